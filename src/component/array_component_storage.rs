@@ -1,12 +1,22 @@
-use crate::component::error::ComponentWriteError;
-use crate::component::{Component, ComponentStorage};
+use crate::component::disjoint::get_many_slots_mut;
+use crate::component::entry::{Entry, OccupiedEntry, VacantEntry, VacantSlot};
+use crate::component::error::{ComponentWriteError, ComponentWriteErrorKind, GetManyMutError};
+use crate::component::memory_usage::MemoryUsage;
+use crate::component::ordered::{
+    first_of_slots, last_of_slots, next_of_slots_after, prev_of_slots_before, range_of_slots,
+};
+use crate::component::{
+    Component, ComponentIter, ComponentIterMut, ComponentStorage, OrderedComponentStorage,
+};
 use std::array::from_fn;
 use std::mem;
+use std::ops::Range;
 
 /// an implementation of `ComponentStorage` that uses fixed-size arrays as backing memory
 #[derive(Debug)]
 pub struct ArrayComponentStorage<T: Component, const SIZE: usize> {
     components: [Option<T>; SIZE],
+    len: usize,
 }
 
 impl<T, const SIZE: usize> ComponentStorage<T> for ArrayComponentStorage<T, SIZE>
@@ -20,22 +30,177 @@ where
         None
     }
 
+    fn get_mut(&mut self, index: usize) -> Option<&'_ mut T> {
+        if let Some(Some(component)) = self.components.get_mut(index) {
+            return Some(component);
+        }
+        None
+    }
+
+    fn get_many_mut_into<'a>(
+        &'a mut self,
+        indices: &[usize],
+        components: &mut [Option<&'a mut T>],
+    ) -> Result<(), GetManyMutError> {
+        // SAFETY: each index is its own slot
+        unsafe {
+            get_many_slots_mut(
+                &mut self.components,
+                indices,
+                components,
+                Some,
+                Option::as_mut,
+            )
+        }
+    }
+
+    unsafe fn get_unchecked(&self, index: usize) -> &T {
+        // SAFETY: the caller guarantees a component is stored at the index
+        unsafe {
+            self.components
+                .get_unchecked(index)
+                .as_ref()
+                .unwrap_unchecked()
+        }
+    }
+
+    unsafe fn get_unchecked_mut(&mut self, index: usize) -> &mut T {
+        // SAFETY: the caller guarantees a component is stored at the index
+        unsafe {
+            self.components
+                .get_unchecked_mut(index)
+                .as_mut()
+                .unwrap_unchecked()
+        }
+    }
+
     fn insert(&mut self, index: usize, component: T) -> Result<Option<T>, ComponentWriteError> {
         if let Some(stored) = self.components.get_mut(index) {
             let mut tmp = Some(component);
             mem::swap(stored, &mut tmp);
+            if tmp.is_none() {
+                self.len += 1;
+            }
             return Ok(tmp);
         }
-        Err(ComponentWriteError::new::<T>(index))
+        Err(ComponentWriteError::new_with_kind::<T>(
+            index,
+            ComponentWriteErrorKind::OutOfBounds { capacity: SIZE },
+        ))
     }
 
     fn delete(&mut self, index: usize) -> Result<Option<T>, ComponentWriteError> {
         if let Some(stored) = self.components.get_mut(index) {
             let mut tmp = None;
             mem::swap(stored, &mut tmp);
+            if tmp.is_some() {
+                self.len -= 1;
+            }
             return Ok(tmp);
         }
-        Err(ComponentWriteError::new::<T>(index))
+        Err(ComponentWriteError::new_with_kind::<T>(
+            index,
+            ComponentWriteErrorKind::OutOfBounds { capacity: SIZE },
+        ))
+    }
+
+    fn entry(&mut self, index: usize) -> Result<Entry<'_, T>, ComponentWriteError> {
+        let len = &mut self.len;
+        match self.components.get_mut(index) {
+            Some(Some(component)) => Ok(Entry::Occupied(OccupiedEntry::new(index, component))),
+            Some(slot @ None) => Ok(Entry::Vacant(VacantEntry::with_slot(
+                index,
+                VacantSlot::Option {
+                    slot,
+                    len,
+                    page_len: None,
+                },
+            ))),
+            None => Err(ComponentWriteError::new_with_kind::<T>(
+                index,
+                ComponentWriteErrorKind::OutOfBounds { capacity: SIZE },
+            )),
+        }
+    }
+
+    fn iter(&self) -> ComponentIter<'_, T> {
+        Box::new(
+            self.components
+                .iter()
+                .enumerate()
+                .filter_map(|(index, component)| Some((index, component.as_ref()?))),
+        )
+    }
+
+    fn iter_mut(&mut self) -> ComponentIterMut<'_, T> {
+        Box::new(
+            self.components
+                .iter_mut()
+                .enumerate()
+                .filter_map(|(index, component)| Some((index, component.as_mut()?))),
+        )
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn clear(&mut self) {
+        self.components.fill_with(|| None);
+        self.len = 0;
+    }
+
+    fn capacity(&self) -> usize {
+        SIZE
+    }
+
+    fn reserve(&mut self, _additional: usize) {
+        // the backing memory is a fixed-size array, so there's nothing to reserve
+    }
+
+    fn shrink_to_fit(&mut self) {
+        // the backing memory is a fixed-size array, so there's nothing to release
+    }
+
+    fn memory_usage(&self) -> MemoryUsage {
+        // every slot lives inside the storage itself
+        MemoryUsage::new::<Self>(0)
+    }
+
+    fn retain(&mut self, predicate: &mut dyn FnMut(usize, &mut T) -> bool) {
+        for (index, stored) in self.components.iter_mut().enumerate() {
+            if let Some(component) = stored {
+                if !predicate(index, component) {
+                    *stored = None;
+                    self.len -= 1;
+                }
+            }
+        }
+    }
+}
+
+impl<T, const SIZE: usize> OrderedComponentStorage<T> for ArrayComponentStorage<T, SIZE>
+where
+    T: Component,
+{
+    fn range(&self, range: Range<usize>) -> ComponentIter<'_, T> {
+        range_of_slots(&self.components, range)
+    }
+
+    fn first(&self) -> Option<(usize, &T)> {
+        first_of_slots(&self.components)
+    }
+
+    fn last(&self) -> Option<(usize, &T)> {
+        last_of_slots(&self.components)
+    }
+
+    fn next_after(&self, index: usize) -> Option<(usize, &T)> {
+        next_of_slots_after(&self.components, index)
+    }
+
+    fn prev_before(&self, index: usize) -> Option<(usize, &T)> {
+        prev_of_slots_before(&self.components, index)
     }
 }
 
@@ -46,6 +211,7 @@ where
     fn default() -> Self {
         Self {
             components: from_fn(|_| None),
+            len: 0,
         }
     }
 }
@@ -53,22 +219,55 @@ where
 #[cfg(test)]
 mod test {
     use crate::component::array_component_storage::ArrayComponentStorage;
-    use crate::component::test::{
-        test_init_behavior, test_insert_and_update_behavior, TestComponent,
-    };
+    use crate::component::error::ComponentWriteErrorKind;
+    use crate::component::testing::{test_conformance, test_ordered_behavior, TestComponent};
     use crate::component::ComponentStorage;
+    use std::mem::size_of;
 
     #[test]
-    fn test_init() {
-        let storage: ArrayComponentStorage<TestComponent, 64> = ArrayComponentStorage::default();
-        test_init_behavior(&storage, 0..64);
+    fn test_conformance_suite() {
+        test_conformance(
+            ArrayComponentStorage::<TestComponent, 64>::default,
+            Some(64),
+        );
     }
 
     #[test]
-    fn test_insert_update() {
+    fn test_ordered() {
         let mut storage: ArrayComponentStorage<TestComponent, 64> =
             ArrayComponentStorage::default();
-        test_insert_and_update_behavior(&mut storage, 0..64);
+        test_ordered_behavior(&mut storage);
+    }
+
+    #[test]
+    fn test_err_on_entry_out_of_bounds() {
+        let mut storage: ArrayComponentStorage<TestComponent, 64> =
+            ArrayComponentStorage::default();
+        assert!(storage.entry(64).is_err())
+    }
+
+    #[test]
+    fn test_err_on_extend_out_of_bounds() {
+        let mut storage: ArrayComponentStorage<TestComponent, 64> =
+            ArrayComponentStorage::default();
+        let mut components = (60..70).map(|i| (i, TestComponent(i as i32)));
+        assert!(storage.extend(&mut components).is_err());
+        assert_eq!(
+            storage.len(),
+            4,
+            "components before the failure were not stored"
+        );
+    }
+
+    #[test]
+    fn test_capacity_is_size() {
+        let mut storage: ArrayComponentStorage<TestComponent, 64> =
+            ArrayComponentStorage::default();
+        assert_eq!(storage.capacity(), 64);
+        storage.reserve(1000);
+        assert_eq!(storage.capacity(), 64);
+        storage.shrink_to_fit();
+        assert_eq!(storage.capacity(), 64);
     }
 
     #[test]
@@ -81,6 +280,20 @@ mod test {
     fn test_err_on_insert_out_of_bounds() {
         let mut storage: ArrayComponentStorage<TestComponent, 64> =
             ArrayComponentStorage::default();
-        assert!(storage.insert(64, TestComponent(0)).is_err())
+        match storage.insert(64, TestComponent(0)) {
+            Ok(x) => panic!("inserting out of bounds returned {:?}", x),
+            Err(err) => assert_eq!(
+                err.kind(),
+                ComponentWriteErrorKind::OutOfBounds { capacity: 64 }
+            ),
+        }
+    }
+
+    #[test]
+    fn test_memory_usage() {
+        let storage: ArrayComponentStorage<TestComponent, 64> = ArrayComponentStorage::default();
+        let usage = storage.memory_usage();
+        assert_eq!(usage.heap_bytes, 0);
+        assert!(usage.inline_bytes >= 64 * size_of::<Option<TestComponent>>());
     }
 }
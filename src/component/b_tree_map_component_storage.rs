@@ -1,6 +1,13 @@
-use crate::component::error::ComponentWriteError;
-use crate::component::{Component, ComponentStorage};
-use std::collections::BTreeMap;
+use crate::component::disjoint::clear_and_check_disjoint;
+use crate::component::entry::{Entry, OccupiedEntry, VacantEntry, VacantSlot};
+use crate::component::error::{ComponentWriteError, GetManyMutError};
+use crate::component::memory_usage::{b_tree_map_heap_bytes, MemoryUsage};
+use crate::component::{
+    Component, ComponentIter, ComponentIterMut, ComponentStorage, OrderedComponentStorage,
+};
+use std::collections::{btree_map, BTreeMap};
+use std::iter;
+use std::ops::{Bound, Range};
 
 pub struct BTreeMapComponentStorage<T: Component> {
     components: BTreeMap<usize, T>,
@@ -10,6 +17,7 @@ impl<T> BTreeMapComponentStorage<T>
 where
     T: Component,
 {
+    /// create a new, empty `BTreeMapComponentStorage`
     pub fn new() -> Self {
         Default::default()
     }
@@ -23,6 +31,36 @@ where
         self.components.get(&index)
     }
 
+    fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        self.components.get_mut(&index)
+    }
+
+    fn get_many_mut_into<'a>(
+        &'a mut self,
+        indices: &[usize],
+        components: &mut [Option<&'a mut T>],
+    ) -> Result<(), GetManyMutError> {
+        clear_and_check_disjoint::<T>(indices, components)?;
+        let mut requests: Vec<(usize, usize)> = indices.iter().copied().zip(0..).collect();
+        requests.sort_unstable();
+        let (Some((first, _)), Some((last, _))) = (requests.first(), requests.last()) else {
+            return Ok(());
+        };
+        // looking each index up separately would reborrow the whole tree every time, so walk
+        // the entries between the lowest and highest requested index instead
+        let mut requests = requests.iter().peekable();
+        for (index, component) in self.components.range_mut(*first..=*last) {
+            while requests
+                .next_if(|(requested, _)| requested < index)
+                .is_some()
+            {}
+            if let Some((_, position)) = requests.next_if(|(requested, _)| requested == index) {
+                components[*position] = Some(component);
+            }
+        }
+        Ok(())
+    }
+
     fn insert(&mut self, index: usize, component: T) -> Result<Option<T>, ComponentWriteError> {
         Ok(self.components.insert(index, component))
     }
@@ -30,6 +68,122 @@ where
     fn delete(&mut self, index: usize) -> Result<Option<T>, ComponentWriteError> {
         Ok(self.components.remove(&index))
     }
+
+    fn entry(&mut self, index: usize) -> Result<Entry<'_, T>, ComponentWriteError> {
+        Ok(match self.components.entry(index) {
+            btree_map::Entry::Occupied(entry) => {
+                Entry::Occupied(OccupiedEntry::new(index, entry.into_mut()))
+            }
+            btree_map::Entry::Vacant(entry) => {
+                Entry::Vacant(VacantEntry::with_slot(index, VacantSlot::BTreeMap(entry)))
+            }
+        })
+    }
+
+    fn iter(&self) -> ComponentIter<'_, T> {
+        Box::new(
+            self.components
+                .iter()
+                .map(|(index, component)| (*index, component)),
+        )
+    }
+
+    fn iter_mut(&mut self) -> ComponentIterMut<'_, T> {
+        Box::new(
+            self.components
+                .iter_mut()
+                .map(|(index, component)| (*index, component)),
+        )
+    }
+
+    fn len(&self) -> usize {
+        self.components.len()
+    }
+
+    fn contains(&self, index: usize) -> bool {
+        self.components.contains_key(&index)
+    }
+
+    fn clear(&mut self) {
+        self.components.clear();
+    }
+
+    fn capacity(&self) -> usize {
+        // a `BTreeMap` allocates a node at a time, so it never holds room for more components
+        self.components.len()
+    }
+
+    fn reserve(&mut self, _additional: usize) {
+        // a `BTreeMap` allocates a node at a time, so there's nothing to reserve
+    }
+
+    fn shrink_to_fit(&mut self) {
+        // a `BTreeMap` frees nodes as soon as they're empty, so there's nothing to release
+    }
+
+    fn memory_usage(&self) -> MemoryUsage {
+        MemoryUsage::new::<Self>(b_tree_map_heap_bytes::<usize, T>(self.components.len()))
+    }
+
+    fn extend(
+        &mut self,
+        components: &mut dyn Iterator<Item = (usize, T)>,
+    ) -> Result<(), ComponentWriteError> {
+        // building a `BTreeMap` from an iterator sorts the entries and builds the tree in bulk,
+        // which is much faster than inserting them one at a time
+        let mut batch: BTreeMap<usize, T> = components.collect();
+        if batch.len() >= self.components.len() {
+            self.components.append(&mut batch);
+        } else {
+            self.components.extend(batch);
+        }
+        Ok(())
+    }
+
+    fn retain(&mut self, predicate: &mut dyn FnMut(usize, &mut T) -> bool) {
+        self.components
+            .retain(|index, component| predicate(*index, component));
+    }
+}
+
+impl<T> OrderedComponentStorage<T> for BTreeMapComponentStorage<T>
+where
+    T: Component,
+{
+    fn range(&self, range: Range<usize>) -> ComponentIter<'_, T> {
+        if range.start >= range.end {
+            // `BTreeMap::range` panics on decreasing ranges
+            return Box::new(iter::empty());
+        }
+        Box::new(
+            self.components
+                .range(range)
+                .map(|(index, component)| (*index, component)),
+        )
+    }
+
+    fn first(&self) -> Option<(usize, &T)> {
+        let (index, component) = self.components.first_key_value()?;
+        Some((*index, component))
+    }
+
+    fn last(&self) -> Option<(usize, &T)> {
+        let (index, component) = self.components.last_key_value()?;
+        Some((*index, component))
+    }
+
+    fn next_after(&self, index: usize) -> Option<(usize, &T)> {
+        let (index, component) = self
+            .components
+            .range((Bound::Excluded(index), Bound::Unbounded))
+            .next()?;
+        Some((*index, component))
+    }
+
+    fn prev_before(&self, index: usize) -> Option<(usize, &T)> {
+        let (index, component) = self.components.range(..index).next_back()?;
+        Some((*index, component))
+    }
 }
 
 impl<T> Default for BTreeMapComponentStorage<T>
@@ -46,22 +200,20 @@ where
 #[cfg(test)]
 mod test {
     use crate::component::b_tree_map_component_storage::BTreeMapComponentStorage;
-    use crate::component::test::{
-        test_init_behavior, test_insert_and_update_behavior, TestComponent,
-    };
+    use crate::component::testing::{test_conformance, test_ordered_behavior, TestComponent};
     use crate::component::ComponentStorage;
+    use std::mem::size_of;
 
     #[test]
-    fn test_init() {
-        let storage: BTreeMapComponentStorage<TestComponent> = BTreeMapComponentStorage::default();
-        test_init_behavior(&storage, 0..64);
+    fn test_conformance_suite() {
+        test_conformance(BTreeMapComponentStorage::default, None);
     }
 
     #[test]
-    fn test_insert_update() {
+    fn test_ordered() {
         let mut storage: BTreeMapComponentStorage<TestComponent> =
             BTreeMapComponentStorage::default();
-        test_insert_and_update_behavior(&mut storage, 0..64);
+        test_ordered_behavior(&mut storage);
     }
 
     #[test]
@@ -97,4 +249,20 @@ mod test {
             ),
         }
     }
+
+    #[test]
+    fn test_memory_usage() {
+        let mut storage: BTreeMapComponentStorage<TestComponent> =
+            BTreeMapComponentStorage::default();
+        assert_eq!(storage.memory_usage().heap_bytes, 0);
+        let _ = storage.insert(3, TestComponent(1));
+        let one = storage.memory_usage().heap_bytes;
+        assert!(one >= 11 * (size_of::<usize>() + size_of::<TestComponent>()));
+        for index in 0..100 {
+            let _ = storage.insert(index, TestComponent(1));
+        }
+        assert!(storage.memory_usage().heap_bytes > one);
+        storage.clear();
+        assert_eq!(storage.memory_usage().heap_bytes, 0);
+    }
 }
@@ -0,0 +1,235 @@
+//! a growable set of indices stored as one bit per index
+
+use crate::component::memory_usage::vec_heap_bytes;
+use std::iter::Copied;
+use std::slice::Iter;
+
+/// a growable set of indices, stored as packed 64-bit words so that sets can be combined
+/// 64 indices at a time
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Bitset {
+    words: Vec<u64>,
+}
+
+impl Bitset {
+    /// the number of indices stored in each word
+    pub const WORD_BITS: usize = u64::BITS as usize;
+
+    /// create a new, empty `Bitset`
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// create a new `Bitset` with room for a certain number of indices reserved in advance
+    /// (it can still grow beyond this initial size)
+    pub fn new_with_initial_size(initial_size: usize) -> Self {
+        Self {
+            words: Vec::with_capacity(initial_size.div_ceil(Self::WORD_BITS)),
+        }
+    }
+
+    /// check whether an index is in the set
+    pub fn contains(&self, index: usize) -> bool {
+        match self.words.get(index / Self::WORD_BITS) {
+            Some(word) => word & (1 << (index % Self::WORD_BITS)) != 0,
+            None => false,
+        }
+    }
+
+    /// add an index to the set, returning whether it was newly added
+    pub fn insert(&mut self, index: usize) -> bool {
+        let word_index = index / Self::WORD_BITS;
+        if word_index >= self.words.len() {
+            self.words.resize(word_index + 1, 0);
+        }
+        let mask = 1 << (index % Self::WORD_BITS);
+        let word = &mut self.words[word_index];
+        let added = *word & mask == 0;
+        *word |= mask;
+        added
+    }
+
+    /// remove an index from the set, returning whether it was present
+    pub fn remove(&mut self, index: usize) -> bool {
+        match self.words.get_mut(index / Self::WORD_BITS) {
+            Some(word) => {
+                let mask = 1 << (index % Self::WORD_BITS);
+                let removed = *word & mask != 0;
+                *word &= !mask;
+                removed
+            }
+            None => false,
+        }
+    }
+
+    /// remove every index from the set (keeping the allocated memory)
+    pub fn clear(&mut self) {
+        self.words.fill(0);
+    }
+
+    /// get the number of indices the set can hold without allocating more memory
+    pub fn capacity(&self) -> usize {
+        self.words.capacity() * Self::WORD_BITS
+    }
+
+    /// reserve memory for at least `additional` more indices beyond the highest one the set
+    /// can currently hold
+    pub fn reserve(&mut self, additional: usize) {
+        self.words.reserve(additional.div_ceil(Self::WORD_BITS));
+    }
+
+    /// get the number of bytes the set has allocated on the heap
+    pub fn heap_bytes(&self) -> usize {
+        vec_heap_bytes::<u64>(self.words.capacity())
+    }
+
+    /// release memory used by words beyond the highest index in the set
+    pub fn shrink_to_fit(&mut self) {
+        let used_words = self
+            .words
+            .iter()
+            .rposition(|word| *word != 0)
+            .map_or(0, |last| last + 1);
+        self.words.truncate(used_words);
+        self.words.shrink_to_fit();
+    }
+
+    /// get the highest index in the set
+    pub fn last(&self) -> Option<usize> {
+        let word_index = self.words.iter().rposition(|word| *word != 0)?;
+        let bit = Self::WORD_BITS - 1 - self.words[word_index].leading_zeros() as usize;
+        Some(word_index * Self::WORD_BITS + bit)
+    }
+
+    /// count the indices in the set
+    pub fn count(&self) -> usize {
+        self.words
+            .iter()
+            .map(|word| word.count_ones() as usize)
+            .sum()
+    }
+
+    /// get the packed words backing the set, where bit `i` of word `w` represents
+    /// index `w * 64 + i`
+    pub fn words(&self) -> &[u64] {
+        &self.words
+    }
+
+    /// iterate over the indices in the set, in ascending order
+    pub fn iter(&self) -> BitIter<Copied<Iter<'_, u64>>> {
+        BitIter::new(self.words.iter().copied())
+    }
+}
+
+/// an iterator over the set bits in a sequence of 64-bit words, yielding the index of each
+/// set bit in ascending order
+///
+/// this can iterate over a combination of several `Bitset`s, e.g. the intersection of two
+/// occupancy masks:
+/// `BitIter::new(a.words().iter().zip(b.words()).map(|(a, b)| a & b))`
+#[derive(Clone, Debug)]
+pub struct BitIter<I: Iterator<Item = u64>> {
+    words: I,
+    current: u64,
+    offset: usize,
+}
+
+impl<I> BitIter<I>
+where
+    I: Iterator<Item = u64>,
+{
+    /// create a new `BitIter` over a sequence of words
+    pub fn new(words: I) -> Self {
+        Self {
+            words,
+            current: 0,
+            offset: 0usize.wrapping_sub(Bitset::WORD_BITS),
+        }
+    }
+}
+
+impl<I> Iterator for BitIter<I>
+where
+    I: Iterator<Item = u64>,
+{
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        while self.current == 0 {
+            self.current = self.words.next()?;
+            self.offset = self.offset.wrapping_add(Bitset::WORD_BITS);
+        }
+        let bit = self.current.trailing_zeros() as usize;
+        self.current &= self.current - 1;
+        Some(self.offset + bit)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::component::bitset::{BitIter, Bitset};
+
+    #[test]
+    fn test_insert_contains_remove() {
+        let mut bitset = Bitset::new();
+        assert!(!bitset.contains(100));
+        assert!(bitset.insert(100), "inserting a new index returned false");
+        assert!(
+            !bitset.insert(100),
+            "inserting an existing index returned true"
+        );
+        assert!(bitset.contains(100));
+        assert!(!bitset.contains(99));
+        assert!(
+            bitset.remove(100),
+            "removing an existing index returned false"
+        );
+        assert!(
+            !bitset.remove(100),
+            "removing a missing index returned true"
+        );
+        assert!(
+            !bitset.remove(123456),
+            "removing an out of range index returned true"
+        );
+        assert!(!bitset.contains(100));
+    }
+
+    #[test]
+    fn test_iter() {
+        let mut bitset = Bitset::new();
+        let indices = [0, 1, 63, 64, 65, 200, 1000];
+        for i in indices {
+            bitset.insert(i);
+        }
+        assert_eq!(bitset.iter().collect::<Vec<_>>(), indices);
+        assert_eq!(bitset.count(), indices.len());
+        assert_eq!(bitset.last(), Some(1000));
+    }
+
+    #[test]
+    fn test_shrink_to_fit() {
+        let mut bitset = Bitset::new();
+        bitset.insert(3);
+        bitset.insert(10_000);
+        bitset.remove(10_000);
+        bitset.shrink_to_fit();
+        assert_eq!(bitset.words().len(), 1);
+        assert_eq!(bitset.last(), Some(3));
+        assert!(bitset.contains(3));
+    }
+
+    #[test]
+    fn test_intersection() {
+        let mut a = Bitset::new();
+        let mut b = Bitset::new();
+        for i in [1, 5, 70, 130] {
+            a.insert(i);
+        }
+        for i in [5, 6, 130, 500] {
+            b.insert(i);
+        }
+        let joined = BitIter::new(a.words().iter().zip(b.words()).map(|(a, b)| a & b));
+        assert_eq!(joined.collect::<Vec<_>>(), [5, 130]);
+    }
+}
@@ -0,0 +1,350 @@
+use crate::component::bitset::{BitIter, Bitset};
+use crate::component::disjoint::get_many_slots_mut;
+use crate::component::entry::{Entry, OccupiedEntry, VacantEntry, VacantSlot};
+use crate::component::error::{ComponentWriteError, ComponentWriteErrorKind, GetManyMutError};
+use crate::component::memory_usage::MemoryUsage;
+use crate::component::{Component, ComponentIter, ComponentIterMut, ComponentStorage};
+use std::fmt::{Debug, Formatter};
+use std::mem::{self, MaybeUninit};
+
+/// an implementation of `ComponentStorage` that uses fixed-size arrays of uninitialized memory
+/// as backing memory, tracking which slots are occupied in a separate `Bitset`
+pub struct BitsetArrayComponentStorage<T: Component, const SIZE: usize> {
+    // invariant: every index in `occupancy` is below `SIZE` and initialized
+    occupancy: Bitset,
+    len: usize,
+    components: [MaybeUninit<T>; SIZE],
+}
+
+impl<T, const SIZE: usize> BitsetArrayComponentStorage<T, SIZE>
+where
+    T: Component,
+{
+    /// get the set of occupied indices, which can be combined with the occupancy of other
+    /// storages to find entities that have several components
+    pub fn occupancy(&self) -> &Bitset {
+        &self.occupancy
+    }
+}
+
+impl<T, const SIZE: usize> ComponentStorage<T> for BitsetArrayComponentStorage<T, SIZE>
+where
+    T: Component,
+{
+    fn get(&self, index: usize) -> Option<&T> {
+        if self.occupancy.contains(index) {
+            // SAFETY: occupied slots are always in bounds and initialized
+            return Some(unsafe { self.components.get_unchecked(index).assume_init_ref() });
+        }
+        None
+    }
+
+    fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        if self.occupancy.contains(index) {
+            // SAFETY: occupied slots are always in bounds and initialized
+            return Some(unsafe { self.components.get_unchecked_mut(index).assume_init_mut() });
+        }
+        None
+    }
+
+    fn get_many_mut_into<'a>(
+        &'a mut self,
+        indices: &[usize],
+        components: &mut [Option<&'a mut T>],
+    ) -> Result<(), GetManyMutError> {
+        let occupancy = &self.occupancy;
+        // SAFETY: each index is its own slot, and only occupied slots (which are always
+        // initialized) are projected
+        unsafe {
+            get_many_slots_mut(
+                &mut self.components,
+                indices,
+                components,
+                |index| occupancy.contains(index).then_some(index),
+                |slot| Some(slot.assume_init_mut()),
+            )
+        }
+    }
+
+    unsafe fn get_unchecked(&self, index: usize) -> &T {
+        // SAFETY: the caller guarantees the slot is occupied, so it's in bounds and initialized
+        unsafe { self.components.get_unchecked(index).assume_init_ref() }
+    }
+
+    unsafe fn get_unchecked_mut(&mut self, index: usize) -> &mut T {
+        // SAFETY: the caller guarantees the slot is occupied, so it's in bounds and initialized
+        unsafe { self.components.get_unchecked_mut(index).assume_init_mut() }
+    }
+
+    fn insert(&mut self, index: usize, component: T) -> Result<Option<T>, ComponentWriteError> {
+        if let Some(slot) = self.components.get_mut(index) {
+            if self.occupancy.insert(index) {
+                slot.write(component);
+                self.len += 1;
+                return Ok(None);
+            }
+            // SAFETY: the slot was already occupied, so it is initialized
+            return Ok(Some(mem::replace(
+                unsafe { slot.assume_init_mut() },
+                component,
+            )));
+        }
+        Err(ComponentWriteError::new_with_kind::<T>(
+            index,
+            ComponentWriteErrorKind::OutOfBounds { capacity: SIZE },
+        ))
+    }
+
+    fn delete(&mut self, index: usize) -> Result<Option<T>, ComponentWriteError> {
+        if index >= SIZE {
+            return Err(ComponentWriteError::new_with_kind::<T>(
+                index,
+                ComponentWriteErrorKind::OutOfBounds { capacity: SIZE },
+            ));
+        }
+        if self.occupancy.remove(index) {
+            self.len -= 1;
+            // SAFETY: the slot was occupied, and is now marked empty so it won't be read again
+            return Ok(Some(unsafe {
+                self.components.get_unchecked(index).assume_init_read()
+            }));
+        }
+        Ok(None)
+    }
+
+    fn entry(&mut self, index: usize) -> Result<Entry<'_, T>, ComponentWriteError> {
+        if index >= SIZE {
+            return Err(ComponentWriteError::new_with_kind::<T>(
+                index,
+                ComponentWriteErrorKind::OutOfBounds { capacity: SIZE },
+            ));
+        }
+        if self.occupancy.contains(index) {
+            // SAFETY: occupied slots are always in bounds and initialized
+            let component = unsafe { self.components.get_unchecked_mut(index).assume_init_mut() };
+            return Ok(Entry::Occupied(OccupiedEntry::new(index, component)));
+        }
+        Ok(Entry::Vacant(VacantEntry::with_slot(
+            index,
+            VacantSlot::Uninit {
+                slot: &mut self.components[index],
+                occupancy: &mut self.occupancy,
+                len: &mut self.len,
+            },
+        )))
+    }
+
+    fn iter(&self) -> ComponentIter<'_, T> {
+        let components = &self.components;
+        Box::new(self.occupancy.iter().map(move |index| {
+            // SAFETY: occupied slots are always in bounds and initialized
+            (index, unsafe {
+                components.get_unchecked(index).assume_init_ref()
+            })
+        }))
+    }
+
+    fn iter_mut(&mut self) -> ComponentIterMut<'_, T> {
+        let components = self.components.as_mut_ptr();
+        Box::new(self.occupancy.iter().map(move |index| {
+            // SAFETY: occupied slots are always in bounds and initialized, and each index is
+            // yielded once, so no two of the returned references alias
+            (index, unsafe { (*components.add(index)).assume_init_mut() })
+        }))
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn contains(&self, index: usize) -> bool {
+        self.occupancy.contains(index)
+    }
+
+    fn clear(&mut self) {
+        // empty the storage before dropping anything, so that a panicking `Drop` leaks the
+        // remaining components instead of leaving them to be dropped again
+        let mut occupancy = mem::take(&mut self.occupancy);
+        self.len = 0;
+        for index in occupancy.iter() {
+            // SAFETY: occupied slots are always in bounds and initialized, and no longer marked
+            // as occupied so they won't be read again
+            unsafe { self.components.get_unchecked_mut(index).assume_init_drop() };
+        }
+        // keep the bitset's memory for reuse
+        occupancy.clear();
+        self.occupancy = occupancy;
+    }
+
+    fn capacity(&self) -> usize {
+        SIZE
+    }
+
+    fn reserve(&mut self, _additional: usize) {
+        // the backing memory is a fixed-size array, so there's nothing to reserve
+    }
+
+    fn shrink_to_fit(&mut self) {
+        // the backing memory is a fixed-size array, so there's nothing to release
+    }
+
+    fn memory_usage(&self) -> MemoryUsage {
+        // the components live inside the storage itself, but the occupancy bits don't
+        MemoryUsage::new::<Self>(self.occupancy.heap_bytes())
+    }
+
+    fn retain(&mut self, predicate: &mut dyn FnMut(usize, &mut T) -> bool) {
+        for word_index in 0..self.occupancy.words().len() {
+            let offset = word_index * Bitset::WORD_BITS;
+            for index in BitIter::new([self.occupancy.words()[word_index]].into_iter()) {
+                let index = offset + index;
+                // SAFETY: occupied slots are always in bounds and initialized
+                let component = unsafe { self.components.get_unchecked_mut(index) };
+                if !predicate(index, unsafe { component.assume_init_mut() }) {
+                    self.occupancy.remove(index);
+                    self.len -= 1;
+                    // SAFETY: the slot is no longer marked occupied, so it won't be read again
+                    unsafe { component.assume_init_drop() };
+                }
+            }
+        }
+    }
+}
+
+impl<T, const SIZE: usize> Default for BitsetArrayComponentStorage<T, SIZE>
+where
+    T: Component,
+{
+    fn default() -> Self {
+        Self {
+            occupancy: Bitset::new_with_initial_size(SIZE),
+            len: 0,
+            components: [const { MaybeUninit::uninit() }; SIZE],
+        }
+    }
+}
+
+impl<T, const SIZE: usize> Debug for BitsetArrayComponentStorage<T, SIZE>
+where
+    T: Component,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<T, const SIZE: usize> Drop for BitsetArrayComponentStorage<T, SIZE>
+where
+    T: Component,
+{
+    fn drop(&mut self) {
+        for index in self.occupancy.iter() {
+            // SAFETY: occupied slots are always in bounds and initialized, and the storage is
+            // being dropped so they won't be read again
+            unsafe { self.components.get_unchecked_mut(index).assume_init_drop() };
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::component::bitset_array_component_storage::BitsetArrayComponentStorage;
+    use crate::component::testing::{test_conformance, TestComponent};
+    use crate::component::{Component, ComponentStorage};
+    use std::cell::Cell;
+    use std::panic::{self, AssertUnwindSafe};
+    use std::rc::Rc;
+
+    #[derive(Debug)]
+    struct DropCounter(#[allow(dead_code)] Rc<()>);
+
+    impl Component for DropCounter {}
+
+    #[test]
+    fn test_conformance_suite() {
+        test_conformance(
+            BitsetArrayComponentStorage::<TestComponent, 64>::default,
+            Some(64),
+        );
+    }
+
+    #[test]
+    fn test_err_on_entry_out_of_bounds() {
+        let mut storage: BitsetArrayComponentStorage<TestComponent, 64> =
+            BitsetArrayComponentStorage::default();
+        assert!(storage.entry(64).is_err())
+    }
+
+    #[test]
+    fn test_none_on_get_out_of_bounds() {
+        let storage: BitsetArrayComponentStorage<TestComponent, 64> =
+            BitsetArrayComponentStorage::default();
+        assert!(storage.get(64).is_none())
+    }
+
+    #[test]
+    fn test_err_on_insert_out_of_bounds() {
+        let mut storage: BitsetArrayComponentStorage<TestComponent, 64> =
+            BitsetArrayComponentStorage::default();
+        assert!(storage.insert(64, TestComponent(0)).is_err())
+    }
+
+    /// a component that counts how many times it's dropped, and panics when dropped if asked to
+    #[derive(Debug)]
+    struct PanickingDrop {
+        drops: Rc<Cell<usize>>,
+        panics: bool,
+    }
+
+    impl Drop for PanickingDrop {
+        fn drop(&mut self) {
+            self.drops.set(self.drops.get() + 1);
+            if self.panics {
+                panic!("dropping a component panicked");
+            }
+        }
+    }
+
+    impl Component for PanickingDrop {}
+
+    #[test]
+    fn test_clear_with_panicking_drop() {
+        let drops = Rc::new(Cell::new(0));
+        let mut storage: BitsetArrayComponentStorage<PanickingDrop, 8> = Default::default();
+        for i in 0..3 {
+            let component = PanickingDrop {
+                drops: drops.clone(),
+                panics: i == 2,
+            };
+            assert!(storage.insert(i, component).is_ok());
+        }
+        let result = panic::catch_unwind(AssertUnwindSafe(|| storage.clear()));
+        assert!(
+            result.is_err(),
+            "dropping the last component should have panicked"
+        );
+        assert_eq!(storage.len(), 0);
+        assert!(!storage.contains(0));
+        drop(storage);
+        assert_eq!(drops.get(), 3, "components were dropped more than once");
+    }
+
+    #[test]
+    fn test_drops_stored_components() {
+        let counter = Rc::new(());
+        let mut storage: BitsetArrayComponentStorage<DropCounter, 128> =
+            BitsetArrayComponentStorage::default();
+        for i in [0, 10, 100] {
+            assert!(storage.insert(i, DropCounter(counter.clone())).is_ok());
+        }
+        assert!(storage.insert(10, DropCounter(counter.clone())).is_ok());
+        assert!(storage.delete(0).is_ok());
+        assert_eq!(Rc::strong_count(&counter), 3);
+        drop(storage);
+        assert_eq!(
+            Rc::strong_count(&counter),
+            1,
+            "components were not dropped with the storage"
+        );
+    }
+}
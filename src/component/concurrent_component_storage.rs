@@ -0,0 +1,438 @@
+//! a component storage that can be written to from many threads at once
+
+use crate::component::error::ComponentWriteError;
+use crate::component::{Component, ComponentStorage};
+use std::fmt::{Debug, Formatter};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+/// the number of stripes used by `ConcurrentComponentStorage::new`
+const DEFAULT_STRIPES: usize = 64;
+
+/// one lock and the slots it guards, aligned to a cache line so that threads working on
+/// neighbouring stripes don't contend over the same line
+#[repr(align(64))]
+struct Stripe<T>(RwLock<Vec<Option<T>>>);
+
+/// a component storage that can be read and written through `&self`, so that many threads can
+/// insert, delete and read components at once (e.g. worker threads each spawning their own
+/// entities)
+///
+/// indices (entity IDs) are spread across a fixed number of stripes (index `i` belongs to
+/// stripe `i % stripes`), each guarded by its own `RwLock`, so threads only wait for each other
+/// when they touch indices in the same stripe at the same time. consecutive indices land in
+/// different stripes, so threads working through neighbouring entities rarely contend
+///
+/// components can't be borrowed past the lock guarding them, so this doesn't implement
+/// `ComponentStorage`: components are read with `get` (which clones them), `read` or `update`,
+/// and can be moved into another storage once the concurrent work is done with `drain_into`
+///
+/// # consistency
+///
+/// - every operation on a single index is atomic: it happens entirely before or entirely
+///   after any other operation on the same index, and a thread always sees its own writes
+/// - once an operation returns, its effect is visible to every operation that starts
+///   afterwards (in any thread)
+/// - operations on different indices aren't ordered with respect to each other, so nothing
+///   spanning several indices (such as `for_each`) sees a snapshot of the whole storage: it
+///   sees each stripe as it was at some point during the call
+/// - `len` counts the completed inserts and deletes, so it is exact while no writes are in
+///   progress, but may be momentarily out of date while they are
+/// - a panic inside a closure passed to `read` or `update` doesn't poison the storage: the
+///   component is left as the closure left it
+pub struct ConcurrentComponentStorage<T: Component> {
+    stripes: Box<[Stripe<T>]>,
+    len: AtomicUsize,
+}
+
+impl<T> ConcurrentComponentStorage<T>
+where
+    T: Component,
+{
+    /// create a new, empty `ConcurrentComponentStorage` with the default number of stripes
+    pub fn new() -> Self {
+        Self::new_with_stripes(DEFAULT_STRIPES)
+    }
+
+    /// create a new `ConcurrentComponentStorage` with a certain number of stripes (more stripes
+    /// means less contention between threads, at the cost of a little memory per stripe)
+    pub fn new_with_stripes(stripes: usize) -> Self {
+        assert!(
+            stripes > 0,
+            "a concurrent storage needs at least one stripe"
+        );
+        Self {
+            stripes: (0..stripes)
+                .map(|_| Stripe(RwLock::new(Vec::new())))
+                .collect(),
+            len: AtomicUsize::new(0),
+        }
+    }
+
+    /// get the number of stripes the indices are spread across
+    pub fn stripes(&self) -> usize {
+        self.stripes.len()
+    }
+
+    /// store a component given the index (entity ID), returning the component it replaced
+    pub fn insert(&self, index: usize, component: T) -> Option<T> {
+        let (stripe, slot) = self.locate(index);
+        let mut slots = self.write(stripe);
+        if slot >= slots.len() {
+            slots.resize_with(slot + 1, || None);
+        }
+        let previous = slots[slot].replace(component);
+        if previous.is_none() {
+            self.len.fetch_add(1, Ordering::Relaxed);
+        }
+        previous
+    }
+
+    /// delete a component given the index (entity ID), returning it
+    pub fn delete(&self, index: usize) -> Option<T> {
+        let (stripe, slot) = self.locate(index);
+        let deleted = self.write(stripe).get_mut(slot)?.take();
+        if deleted.is_some() {
+            self.len.fetch_sub(1, Ordering::Relaxed);
+        }
+        deleted
+    }
+
+    /// get a copy of a component given the index (entity ID)
+    pub fn get(&self, index: usize) -> Option<T>
+    where
+        T: Clone,
+    {
+        self.read(index, T::clone)
+    }
+
+    /// check whether a component is stored at the index (entity ID)
+    pub fn contains(&self, index: usize) -> bool {
+        self.read(index, |_| ()).is_some()
+    }
+
+    /// call a function with a reference to a component given the index (entity ID), returning
+    /// its result (other threads can read the same stripe meanwhile, but not write to it)
+    pub fn read<R>(&self, index: usize, f: impl FnOnce(&T) -> R) -> Option<R> {
+        let (stripe, slot) = self.locate(index);
+        let slots = self.read_stripe(stripe);
+        slots.get(slot)?.as_ref().map(f)
+    }
+
+    /// call a function with a mutable reference to a component given the index (entity ID),
+    /// returning its result (no other thread can use the same stripe meanwhile)
+    pub fn update<R>(&self, index: usize, f: impl FnOnce(&mut T) -> R) -> Option<R> {
+        let (stripe, slot) = self.locate(index);
+        let mut slots = self.write(stripe);
+        slots.get_mut(slot)?.as_mut().map(f)
+    }
+
+    /// call a function with every stored component along with its index (entity ID), one
+    /// stripe at a time (so components aren't visited in order of index)
+    pub fn for_each(&self, mut f: impl FnMut(usize, &T)) {
+        for stripe in 0..self.stripes.len() {
+            let slots = self.read_stripe(stripe);
+            for (slot, component) in slots.iter().enumerate() {
+                if let Some(component) = component {
+                    f(self.index(stripe, slot), component);
+                }
+            }
+        }
+    }
+
+    /// get the number of components stored
+    pub fn len(&self) -> usize {
+        self.len.load(Ordering::Relaxed)
+    }
+
+    /// check whether no components are stored
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// delete every stored component, one stripe at a time
+    pub fn clear(&self) {
+        for stripe in 0..self.stripes.len() {
+            let mut slots = self.write(stripe);
+            let deleted = slots.iter().filter(|slot| slot.is_some()).count();
+            slots.clear();
+            self.len.fetch_sub(deleted, Ordering::Relaxed);
+        }
+    }
+
+    /// get a mutable reference to a component given the index (entity ID), without locking
+    /// (exclusive access already rules out other threads)
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        let (stripe, slot) = self.locate(index);
+        Self::slots_mut(&mut self.stripes[stripe])
+            .get_mut(slot)?
+            .as_mut()
+    }
+
+    /// move every stored component into another storage (e.g. once worker threads are done
+    /// inserting), stopping at the first one that fails (components that weren't moved yet
+    /// remain stored here, and the one that failed is dropped)
+    pub fn drain_into(
+        &mut self,
+        storage: &mut dyn ComponentStorage<T>,
+    ) -> Result<(), ComponentWriteError> {
+        let stripe_count = self.stripes.len();
+        let len = self.len.get_mut();
+        // move one component at a time, since a storage's `extend` may take every component
+        // before it fails
+        for (stripe, slots) in self.stripes.iter_mut().enumerate() {
+            for (slot, component) in Self::slots_mut(slots).iter_mut().enumerate() {
+                if let Some(component) = component.take() {
+                    *len -= 1;
+                    storage.insert(slot * stripe_count + stripe, component)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// get the stripe and the slot within it for an index (entity ID)
+    fn locate(&self, index: usize) -> (usize, usize) {
+        (index % self.stripes.len(), index / self.stripes.len())
+    }
+
+    /// get the index (entity ID) for a slot in a stripe
+    fn index(&self, stripe: usize, slot: usize) -> usize {
+        slot * self.stripes.len() + stripe
+    }
+
+    // a panic while a lock is held can only come from a caller's closure, which can't leave
+    // the slots themselves inconsistent, so poisoned locks are used as if they weren't
+
+    fn read_stripe(&self, stripe: usize) -> RwLockReadGuard<'_, Vec<Option<T>>> {
+        self.stripes[stripe]
+            .0
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn write(&self, stripe: usize) -> RwLockWriteGuard<'_, Vec<Option<T>>> {
+        self.stripes[stripe]
+            .0
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn slots_mut(stripe: &mut Stripe<T>) -> &mut Vec<Option<T>> {
+        stripe.0.get_mut().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl<T> Default for ConcurrentComponentStorage<T>
+where
+    T: Component,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Debug for ConcurrentComponentStorage<T>
+where
+    T: Component,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let mut map = f.debug_map();
+        self.for_each(|index, component| {
+            map.entry(&index, component);
+        });
+        map.finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::component::array_component_storage::ArrayComponentStorage;
+    use crate::component::concurrent_component_storage::ConcurrentComponentStorage;
+    use crate::component::testing::TestComponent;
+    use crate::component::tracked_storage::TrackedStorage;
+    use crate::component::vec_component_storage::VecComponentStorage;
+    use crate::component::ComponentStorage;
+    use std::panic::{self, AssertUnwindSafe};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::thread;
+
+    const THREADS: usize = 8;
+
+    #[test]
+    fn test_insert_get_delete() {
+        let storage = ConcurrentComponentStorage::new_with_stripes(4);
+        assert!(storage.is_empty());
+        for index in (0..64).step_by(3) {
+            assert_eq!(storage.insert(index, TestComponent(index as i32)), None);
+        }
+        assert_eq!(storage.len(), 22);
+        assert_eq!(storage.get(9), Some(TestComponent(9)));
+        assert_eq!(storage.get(10), None);
+        assert_eq!(storage.get(1000), None);
+        assert_eq!(storage.insert(9, TestComponent(90)), Some(TestComponent(9)));
+        assert_eq!(storage.update(9, |component| component.0 += 1), Some(()));
+        assert_eq!(storage.read(9, |component| component.0), Some(91));
+        assert_eq!(storage.delete(9), Some(TestComponent(91)));
+        assert_eq!(storage.delete(9), None);
+        assert!(!storage.contains(9));
+        assert_eq!(storage.len(), 21);
+
+        let mut visited = Vec::new();
+        storage.for_each(|index, component| {
+            assert_eq!(component.0, index as i32);
+            visited.push(index);
+        });
+        visited.sort_unstable();
+        let expected: Vec<usize> = (0..64).step_by(3).filter(|index| *index != 9).collect();
+        assert_eq!(visited, expected);
+
+        storage.clear();
+        assert!(storage.is_empty());
+        assert_eq!(storage.get(3), None);
+    }
+
+    #[test]
+    fn test_get_mut() {
+        let mut storage = ConcurrentComponentStorage::new_with_stripes(4);
+        storage.insert(5, TestComponent(1));
+        if let Some(component) = storage.get_mut(5) {
+            component.0 = 2;
+        }
+        assert_eq!(storage.get(5), Some(TestComponent(2)));
+        assert!(storage.get_mut(6).is_none());
+    }
+
+    #[test]
+    fn test_disjoint_inserts_from_many_threads() {
+        let storage = ConcurrentComponentStorage::new_with_stripes(16);
+        let per_thread = 2000;
+        thread::scope(|scope| {
+            for thread in 0..THREADS {
+                let storage = &storage;
+                scope.spawn(move || {
+                    // interleave the threads' indices so that they share every stripe
+                    for i in 0..per_thread {
+                        let index = i * THREADS + thread;
+                        assert_eq!(storage.insert(index, TestComponent(index as i32)), None);
+                    }
+                });
+            }
+        });
+        assert_eq!(storage.len(), THREADS * per_thread);
+        for index in 0..THREADS * per_thread {
+            assert_eq!(storage.get(index), Some(TestComponent(index as i32)));
+        }
+    }
+
+    #[test]
+    fn test_updates_to_one_index_are_atomic() {
+        let storage = ConcurrentComponentStorage::new_with_stripes(4);
+        storage.insert(7, TestComponent(0));
+        let per_thread = 5000;
+        thread::scope(|scope| {
+            for _ in 0..THREADS {
+                scope.spawn(|| {
+                    for _ in 0..per_thread {
+                        storage.update(7, |component| component.0 += 1);
+                    }
+                });
+            }
+        });
+        assert_eq!(
+            storage.get(7),
+            Some(TestComponent((THREADS * per_thread) as i32))
+        );
+    }
+
+    #[test]
+    fn test_contended_inserts_and_deletes() {
+        let storage = ConcurrentComponentStorage::new_with_stripes(4);
+        let inserted = AtomicUsize::new(0);
+        let deleted = AtomicUsize::new(0);
+        let rounds = 2000;
+        let indices = 32;
+        thread::scope(|scope| {
+            for thread in 0..THREADS {
+                let (storage, inserted, deleted) = (&storage, &inserted, &deleted);
+                scope.spawn(move || {
+                    for round in 0..rounds {
+                        let index = (round * 7 + thread) % indices;
+                        if (round + thread) % 2 == 0 {
+                            if storage.insert(index, TestComponent(1)).is_none() {
+                                inserted.fetch_add(1, Ordering::Relaxed);
+                            }
+                        } else if storage.delete(index).is_some() {
+                            deleted.fetch_add(1, Ordering::Relaxed);
+                        }
+                        // a reader sees either no component or a whole one
+                        if let Some(component) = storage.get(index) {
+                            assert_eq!(component, TestComponent(1));
+                        }
+                    }
+                });
+            }
+        });
+        let expected = inserted.load(Ordering::Relaxed) - deleted.load(Ordering::Relaxed);
+        assert_eq!(storage.len(), expected, "len drifted from the net inserts");
+        let mut counted = 0;
+        storage.for_each(|_, _| counted += 1);
+        assert_eq!(counted, expected);
+    }
+
+    #[test]
+    fn test_panicking_closure_does_not_poison() {
+        let storage = ConcurrentComponentStorage::new_with_stripes(1);
+        storage.insert(0, TestComponent(1));
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            storage.update(0, |component| {
+                component.0 = 2;
+                panic!("closure panicked");
+            })
+        }));
+        assert!(result.is_err());
+        assert_eq!(storage.get(0), Some(TestComponent(2)));
+        storage.insert(1, TestComponent(3));
+        assert_eq!(storage.len(), 2);
+    }
+
+    #[test]
+    fn test_drain_into() {
+        let mut storage = ConcurrentComponentStorage::new_with_stripes(4);
+        thread::scope(|scope| {
+            for thread in 0..THREADS {
+                let storage = &storage;
+                scope.spawn(move || {
+                    storage.insert(thread * 3, TestComponent(thread as i32));
+                });
+            }
+        });
+        let mut target = VecComponentStorage::<TestComponent>::default();
+        assert!(storage.drain_into(&mut target).is_ok());
+        assert!(storage.is_empty());
+        assert_eq!(target.len(), THREADS);
+        for thread in 0..THREADS {
+            assert_eq!(target.get(thread * 3), Some(&TestComponent(thread as i32)));
+        }
+    }
+
+    #[test]
+    fn test_drain_into_bounded_storage() {
+        let mut storage = ConcurrentComponentStorage::new_with_stripes(1);
+        for index in 0..8 {
+            storage.insert(index, TestComponent(index as i32));
+        }
+        let mut target: TrackedStorage<TestComponent, ArrayComponentStorage<TestComponent, 4>> =
+            TrackedStorage::default();
+        assert!(storage.drain_into(&mut target).is_err());
+        assert_eq!(target.len(), 4);
+        // the component that didn't fit is dropped, but the rest stay where they were
+        assert_eq!(storage.len(), 3);
+        for index in 5..8 {
+            assert_eq!(
+                storage.read(index, TestComponent::clone),
+                Some(TestComponent(index as i32))
+            );
+        }
+    }
+}
@@ -0,0 +1,181 @@
+//! helpers for handing out mutable references to several components at once
+
+use crate::component::error::GetManyMutError;
+use crate::component::ComponentIterMut;
+
+/// the most indices to compare pairwise when looking for duplicates (beyond this, sorting a
+/// copy is cheaper)
+const PAIRWISE_LIMIT: usize = 16;
+
+/// clear every reference in `components`, then check that no index appears twice in `indices`
+///
+/// panics if the two slices are different lengths
+pub(crate) fn clear_and_check_disjoint<T>(
+    indices: &[usize],
+    components: &mut [Option<&mut T>],
+) -> Result<(), GetManyMutError> {
+    assert_eq!(
+        indices.len(),
+        components.len(),
+        "every index needs a matching place for its component"
+    );
+    components.fill_with(|| None);
+    let duplicate = if indices.len() <= PAIRWISE_LIMIT {
+        indices
+            .iter()
+            .enumerate()
+            .find(|(i, index)| indices[..*i].contains(index))
+            .map(|(_, index)| *index)
+    } else {
+        let mut sorted = indices.to_vec();
+        sorted.sort_unstable();
+        sorted
+            .windows(2)
+            .find(|pair| pair[0] == pair[1])
+            .map(|pair| pair[0])
+    };
+    match duplicate {
+        Some(index) => Err(GetManyMutError::new::<T>(index)),
+        None => Ok(()),
+    }
+}
+
+/// fill `components` with mutable references to the components yielded by `iter` whose
+/// indices are requested, for storages that can't find several indices at once any other way
+///
+/// the indices must already have been checked with `clear_and_check_disjoint`
+pub(crate) fn get_many_from_iter<'a, T>(
+    iter: ComponentIterMut<'a, T>,
+    indices: &[usize],
+    components: &mut [Option<&'a mut T>],
+) {
+    let mut positions: Vec<(usize, usize)> = indices.iter().copied().zip(0..).collect();
+    positions.sort_unstable();
+    for (index, component) in iter {
+        if let Ok(found) = positions.binary_search_by_key(&index, |(index, _)| *index) {
+            components[positions[found].1] = Some(component);
+        }
+    }
+}
+
+/// fill `components` with mutable references into `slots`, for storages that keep their
+/// components in one slice: `slot_of` finds the slot for an index (entity ID), and `project`
+/// gets the component out of a slot (if it holds one)
+///
+/// # Safety
+/// `slot_of` must never give the same slot for two different indices
+pub(crate) unsafe fn get_many_slots_mut<'a, S, T>(
+    slots: &'a mut [S],
+    indices: &[usize],
+    components: &mut [Option<&'a mut T>],
+    mut slot_of: impl FnMut(usize) -> Option<usize>,
+    mut project: impl FnMut(&'a mut S) -> Option<&'a mut T>,
+) -> Result<(), GetManyMutError> {
+    clear_and_check_disjoint::<T>(indices, components)?;
+    let len = slots.len();
+    // every reference is made from this one pointer, so making one doesn't invalidate the others
+    let base = slots.as_mut_ptr();
+    for (index, component) in indices.iter().zip(components.iter_mut()) {
+        if let Some(slot) = slot_of(*index).filter(|slot| *slot < len) {
+            // SAFETY: the slot is in bounds, and the indices are distinct, so no other
+            // reference handed out points into the same slot
+            *component = project(unsafe { &mut *base.add(slot) });
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::component::disjoint::clear_and_check_disjoint;
+    use crate::component::entry::Entry;
+    use crate::component::error::ComponentWriteError;
+    use crate::component::testing::{test_get_many_mut_behavior, TestComponent};
+    use crate::component::vec_component_storage::VecComponentStorage;
+    use crate::component::{ComponentIter, ComponentIterMut, ComponentStorage};
+
+    /// a storage that only implements the required methods, to test the default
+    /// `get_many_mut_into`
+    #[derive(Default)]
+    struct MinimalStorage(VecComponentStorage<TestComponent>);
+
+    impl ComponentStorage<TestComponent> for MinimalStorage {
+        fn get(&self, index: usize) -> Option<&TestComponent> {
+            self.0.get(index)
+        }
+
+        fn get_mut(&mut self, index: usize) -> Option<&mut TestComponent> {
+            self.0.get_mut(index)
+        }
+
+        fn insert(
+            &mut self,
+            index: usize,
+            component: TestComponent,
+        ) -> Result<Option<TestComponent>, ComponentWriteError> {
+            self.0.insert(index, component)
+        }
+
+        fn delete(&mut self, index: usize) -> Result<Option<TestComponent>, ComponentWriteError> {
+            self.0.delete(index)
+        }
+
+        fn entry(&mut self, index: usize) -> Result<Entry<'_, TestComponent>, ComponentWriteError> {
+            self.0.entry(index)
+        }
+
+        fn iter(&self) -> ComponentIter<'_, TestComponent> {
+            self.0.iter()
+        }
+
+        fn iter_mut(&mut self) -> ComponentIterMut<'_, TestComponent> {
+            self.0.iter_mut()
+        }
+
+        fn len(&self) -> usize {
+            self.0.len()
+        }
+
+        fn clear(&mut self) {
+            self.0.clear()
+        }
+
+        fn capacity(&self) -> usize {
+            self.0.capacity()
+        }
+
+        fn reserve(&mut self, additional: usize) {
+            self.0.reserve(additional)
+        }
+
+        fn shrink_to_fit(&mut self) {
+            self.0.shrink_to_fit()
+        }
+    }
+
+    #[test]
+    fn test_finds_duplicates() {
+        for len in [2, 16, 17, 100] {
+            let mut indices: Vec<usize> = (0..len).map(|i| i * 3).collect();
+            let mut components: Vec<Option<&mut TestComponent>> = (0..len).map(|_| None).collect();
+            assert!(clear_and_check_disjoint(&indices, &mut components).is_ok());
+            indices[0] = indices[len - 1];
+            match clear_and_check_disjoint(&indices, &mut components) {
+                Ok(()) => panic!("a repeated index among {} was not found", len),
+                Err(err) => assert_eq!(err.entity_id(), (len - 1) * 3),
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_lengths_must_match() {
+        let mut components: [Option<&mut TestComponent>; 1] = [None];
+        let _ = clear_and_check_disjoint(&[1, 2], &mut components);
+    }
+
+    #[test]
+    fn test_default_get_many_mut() {
+        test_get_many_mut_behavior(&mut MinimalStorage::default(), 256);
+    }
+}
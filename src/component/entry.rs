@@ -0,0 +1,248 @@
+//! a view into a single slot of a `ComponentStorage`, which may or may not hold a component
+
+use crate::component::bitset::Bitset;
+use crate::component::Component;
+use std::collections::{btree_map, hash_map};
+use std::fmt::{Debug, Formatter};
+use std::mem::MaybeUninit;
+
+/// a view into a single slot of a `ComponentStorage`, obtained from `ComponentStorage::entry`
+#[derive(Debug)]
+pub enum Entry<'a, T: Component> {
+    Occupied(OccupiedEntry<'a, T>),
+    Vacant(VacantEntry<'a, T>),
+}
+
+impl<'a, T> Entry<'a, T>
+where
+    T: Component,
+{
+    /// get the index (entity ID) of the slot
+    pub fn index(&self) -> usize {
+        match self {
+            Entry::Occupied(entry) => entry.index(),
+            Entry::Vacant(entry) => entry.index(),
+        }
+    }
+
+    /// get the stored component, storing `default` first if the slot is empty
+    pub fn or_insert(self, default: T) -> &'a mut T {
+        match self {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(default),
+        }
+    }
+
+    /// get the stored component, storing the result of `default` first if the slot is empty
+    pub fn or_insert_with<F: FnOnce() -> T>(self, default: F) -> &'a mut T {
+        match self {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(default()),
+        }
+    }
+
+    /// modify the stored component in place if the slot is occupied
+    pub fn and_modify<F: FnOnce(&mut T)>(self, f: F) -> Self {
+        match self {
+            Entry::Occupied(mut entry) => {
+                f(entry.get_mut());
+                Entry::Occupied(entry)
+            }
+            Entry::Vacant(entry) => Entry::Vacant(entry),
+        }
+    }
+
+    /// get the stored component, storing `T::default()` first if the slot is empty
+    pub fn or_default(self) -> &'a mut T
+    where
+        T: Default,
+    {
+        self.or_insert_with(T::default)
+    }
+}
+
+/// a view into a slot of a `ComponentStorage` that holds a component
+pub struct OccupiedEntry<'a, T: Component> {
+    index: usize,
+    component: &'a mut T,
+}
+
+impl<'a, T> OccupiedEntry<'a, T>
+where
+    T: Component,
+{
+    /// create a new `OccupiedEntry` given the index (entity ID) and the component stored there
+    pub fn new(index: usize, component: &'a mut T) -> Self {
+        Self { index, component }
+    }
+
+    /// get the index (entity ID) of the slot
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// get a reference to the stored component
+    pub fn get(&self) -> &T {
+        self.component
+    }
+
+    /// get a mutable reference to the stored component
+    pub fn get_mut(&mut self) -> &mut T {
+        self.component
+    }
+
+    /// convert the entry into a mutable reference to the stored component
+    pub fn into_mut(self) -> &'a mut T {
+        self.component
+    }
+}
+
+impl<T> Debug for OccupiedEntry<'_, T>
+where
+    T: Component,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("OccupiedEntry")
+            .field("index", &self.index)
+            .field("component", &self.component)
+            .finish()
+    }
+}
+
+/// a storage that can store a component at an index (entity ID) its `entry` found empty, for
+/// storages whose vacant entries can't simply hold on to the empty slot
+pub trait InsertVacant<T> {
+    /// store a component at an index that `entry` found empty, returning a mutable reference
+    /// to it (this can't fail, so `entry` must already have checked the index)
+    fn insert_vacant(&mut self, index: usize, component: T) -> &mut T;
+}
+
+/// something (like `TrackedStorage`) that needs to know when a component is stored through a
+/// vacant entry
+pub(crate) trait InsertObserver {
+    fn inserted(&mut self, index: usize);
+}
+
+/// the empty slot a storage found for a vacant entry
+pub(crate) enum VacantSlot<'a, T> {
+    /// an empty slot in a vector or array of optional components, along with the storage's
+    /// length and (for paged storages) the length of the page holding the slot
+    Option {
+        slot: &'a mut Option<T>,
+        len: &'a mut usize,
+        page_len: Option<&'a mut usize>,
+    },
+    /// an uninitialized slot, along with the bitset tracking which slots are initialized (the
+    /// slot must be the one for the entry's index)
+    Uninit {
+        slot: &'a mut MaybeUninit<T>,
+        occupancy: &'a mut Bitset,
+        len: &'a mut usize,
+    },
+    HashMap(hash_map::VacantEntry<'a, usize, T>),
+    BTreeMap(btree_map::VacantEntry<'a, usize, T>),
+    Storage(&'a mut dyn InsertVacant<T>),
+}
+
+/// the observers to notify when a vacant entry is filled
+enum Observers<'a> {
+    None,
+    One(&'a mut dyn InsertObserver),
+    // only needed when observing storages wrap each other, so allocating is fine
+    Many(Vec<&'a mut dyn InsertObserver>),
+}
+
+/// a view into an empty slot of a `ComponentStorage`
+pub struct VacantEntry<'a, T: Component> {
+    index: usize,
+    slot: VacantSlot<'a, T>,
+    observers: Observers<'a>,
+}
+
+impl<'a, T> VacantEntry<'a, T>
+where
+    T: Component,
+{
+    /// create a new `VacantEntry` given the index (entity ID) and the storage it belongs to,
+    /// which must have already checked that it can store a component at that index
+    pub fn new(index: usize, storage: &'a mut dyn InsertVacant<T>) -> Self {
+        Self::with_slot(index, VacantSlot::Storage(storage))
+    }
+
+    /// create a new `VacantEntry` given the index (entity ID) and the empty slot found for it
+    pub(crate) fn with_slot(index: usize, slot: VacantSlot<'a, T>) -> Self {
+        Self {
+            index,
+            slot,
+            observers: Observers::None,
+        }
+    }
+
+    /// notify an observer when a component is stored through the entry
+    pub(crate) fn observed_by(mut self, observer: &'a mut dyn InsertObserver) -> Self {
+        self.observers = match self.observers {
+            Observers::None => Observers::One(observer),
+            Observers::One(first) => Observers::Many(vec![first, observer]),
+            Observers::Many(mut observers) => {
+                observers.push(observer);
+                Observers::Many(observers)
+            }
+        };
+        self
+    }
+
+    /// get the index (entity ID) of the slot
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// store a component in the slot, returning a mutable reference to it
+    pub fn insert(self, component: T) -> &'a mut T {
+        let index = self.index;
+        match self.observers {
+            Observers::None => {}
+            Observers::One(observer) => observer.inserted(index),
+            Observers::Many(observers) => {
+                for observer in observers {
+                    observer.inserted(index);
+                }
+            }
+        }
+        match self.slot {
+            VacantSlot::Option {
+                slot,
+                len,
+                page_len,
+            } => {
+                *len += 1;
+                if let Some(page_len) = page_len {
+                    *page_len += 1;
+                }
+                slot.insert(component)
+            }
+            VacantSlot::Uninit {
+                slot,
+                occupancy,
+                len,
+            } => {
+                occupancy.insert(index);
+                *len += 1;
+                slot.write(component)
+            }
+            VacantSlot::HashMap(entry) => entry.insert(component),
+            VacantSlot::BTreeMap(entry) => entry.insert(component),
+            VacantSlot::Storage(storage) => storage.insert_vacant(index, component),
+        }
+    }
+}
+
+impl<T> Debug for VacantEntry<'_, T>
+where
+    T: Component,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("VacantEntry")
+            .field("index", &self.index)
+            .finish()
+    }
+}
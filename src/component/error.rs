@@ -2,34 +2,108 @@ use std::any::type_name;
 use std::error::Error;
 use std::fmt::{Display, Formatter};
 
+/// the reason that a component couldn't be stored or written
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum ComponentWriteErrorKind {
+    /// the index (entity ID) is beyond the end of a fixed-size storage
+    OutOfBounds { capacity: usize },
+    /// no storage has been registered for the component type
+    StorageNotRegistered,
+    /// the storage has no room left for another component
+    CapacityExceeded,
+    /// the storage refused to store the component
+    Rejected,
+    /// the entity handle is stale (its entity was despawned, and its index may now belong to
+    /// another entity)
+    StaleEntity,
+    /// any other failure
+    Other,
+}
+
+impl Display for ComponentWriteErrorKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ComponentWriteErrorKind::OutOfBounds { capacity } => {
+                write!(f, "index out of bounds (capacity: {})", capacity)
+            }
+            ComponentWriteErrorKind::StorageNotRegistered => {
+                write!(f, "component storage not found")
+            }
+            ComponentWriteErrorKind::CapacityExceeded => write!(f, "storage capacity exceeded"),
+            ComponentWriteErrorKind::Rejected => write!(f, "component rejected by storage"),
+            ComponentWriteErrorKind::StaleEntity => write!(f, "stale entity handle"),
+            ComponentWriteErrorKind::Other => write!(f, "unknown failure"),
+        }
+    }
+}
+
 /// an error representing a failure to store or write to a component
 #[derive(Debug)]
 pub struct ComponentWriteError {
     component_type: &'static str,
     entity_id: usize,
+    kind: ComponentWriteErrorKind,
     detail: Option<&'static str>,
+    source: Option<Box<dyn Error + Send + Sync + 'static>>,
 }
 
 impl ComponentWriteError {
     /// create a new `ComponentWriteError` given the ID of the associated entity
     /// (without further details)
     pub fn new<T>(entity_id: usize) -> Self {
-        Self {
-            component_type: type_name::<T>(),
-            entity_id,
-            detail: None,
-        }
+        Self::new_with_kind::<T>(entity_id, ComponentWriteErrorKind::Other)
     }
 
     /// create a new `ComponentWriteError` given the ID of the associated entity,
     /// and details
     pub fn new_with_detail<T>(entity_id: usize, detail: &'static str) -> Self {
+        Self::new::<T>(entity_id).with_detail(detail)
+    }
+
+    /// create a new `ComponentWriteError` given the ID of the associated entity,
+    /// and the reason for the failure
+    pub fn new_with_kind<T>(entity_id: usize, kind: ComponentWriteErrorKind) -> Self {
         Self {
             component_type: type_name::<T>(),
             entity_id,
-            detail: Some(detail),
+            kind,
+            detail: None,
+            source: None,
         }
     }
+
+    /// attach details to the error
+    pub fn with_detail(mut self, detail: &'static str) -> Self {
+        self.detail = Some(detail);
+        self
+    }
+
+    /// attach the underlying error that caused the failure
+    pub fn with_source<E: Error + Send + Sync + 'static>(mut self, source: E) -> Self {
+        self.source = Some(Box::new(source));
+        self
+    }
+
+    /// get the reason for the failure
+    pub fn kind(&self) -> ComponentWriteErrorKind {
+        self.kind
+    }
+
+    /// get the name of the component type that couldn't be written
+    pub fn component_type(&self) -> &'static str {
+        self.component_type
+    }
+
+    /// get the ID of the entity whose component couldn't be written
+    pub fn entity_id(&self) -> usize {
+        self.entity_id
+    }
+
+    /// get the details of the failure, if any were given
+    pub fn detail(&self) -> Option<&'static str> {
+        self.detail
+    }
 }
 
 impl Display for ComponentWriteError {
@@ -37,16 +111,113 @@ impl Display for ComponentWriteError {
         match self.detail {
             Some(detail) => write!(
                 f,
-                "Failed to write `{}` component for entity {}: {}",
-                self.component_type, self.entity_id, detail
+                "Failed to write `{}` component for entity {}: {} ({})",
+                self.component_type, self.entity_id, detail, self.kind
             ),
             None => write!(
                 f,
-                "Failed to write `{}` component for entity {}.",
-                self.component_type, self.entity_id
+                "Failed to write `{}` component for entity {}: {}.",
+                self.component_type, self.entity_id, self.kind
             ),
         }
     }
 }
 
-impl Error for ComponentWriteError {}
+impl Error for ComponentWriteError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.source
+            .as_deref()
+            .map(|source| source as &(dyn Error + 'static))
+    }
+}
+
+/// an error representing a request for mutable references to several components at once that
+/// named the same index (entity ID) more than once
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct GetManyMutError {
+    component_type: &'static str,
+    entity_id: usize,
+}
+
+impl GetManyMutError {
+    /// create a new `GetManyMutError` given the ID of the entity that was requested twice
+    pub fn new<T>(entity_id: usize) -> Self {
+        Self {
+            component_type: type_name::<T>(),
+            entity_id,
+        }
+    }
+
+    /// get the name of the component type that was requested
+    pub fn component_type(&self) -> &'static str {
+        self.component_type
+    }
+
+    /// get the ID of the entity that was requested more than once
+    pub fn entity_id(&self) -> usize {
+        self.entity_id
+    }
+}
+
+impl Display for GetManyMutError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Requested `{}` component for entity {} more than once.",
+            self.component_type, self.entity_id
+        )
+    }
+}
+
+impl Error for GetManyMutError {}
+
+#[cfg(test)]
+mod test {
+    use crate::component::error::{ComponentWriteError, ComponentWriteErrorKind};
+    use crate::component::testing::TestComponent;
+    use std::error::Error;
+    use std::fmt::{Display, Formatter};
+
+    #[derive(Debug)]
+    struct Cause;
+
+    impl Display for Cause {
+        fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+            write!(f, "disk full")
+        }
+    }
+
+    impl Error for Cause {}
+
+    #[test]
+    fn test_kind() {
+        let err = ComponentWriteError::new_with_kind::<TestComponent>(
+            70,
+            ComponentWriteErrorKind::OutOfBounds { capacity: 64 },
+        );
+        assert_eq!(
+            err.kind(),
+            ComponentWriteErrorKind::OutOfBounds { capacity: 64 }
+        );
+        assert_eq!(err.entity_id(), 70);
+        assert!(err.detail().is_none());
+        assert_eq!(
+            ComponentWriteError::new::<TestComponent>(0).kind(),
+            ComponentWriteErrorKind::Other
+        );
+    }
+
+    #[test]
+    fn test_source() {
+        let err = ComponentWriteError::new_with_kind::<TestComponent>(
+            3,
+            ComponentWriteErrorKind::CapacityExceeded,
+        );
+        assert!(err.source().is_none());
+        let err = err.with_source(Cause);
+        match err.source() {
+            Some(source) => assert_eq!(source.to_string(), "disk full"),
+            None => panic!("wrapped cause was not returned as the source"),
+        }
+    }
+}
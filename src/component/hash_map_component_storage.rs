@@ -1,28 +1,104 @@
-use crate::component::error::ComponentWriteError;
-use crate::component::{Component, ComponentStorage};
-use std::collections::HashMap;
+use crate::component::disjoint::{clear_and_check_disjoint, get_many_from_iter};
+use crate::component::entry::{Entry, OccupiedEntry, VacantEntry, VacantSlot};
+use crate::component::error::{ComponentWriteError, GetManyMutError};
+use crate::component::index_hasher::BuildIndexHasher;
+use crate::component::memory_usage::{hash_map_heap_bytes, MemoryUsage};
+use crate::component::{Component, ComponentIter, ComponentIterMut, ComponentStorage};
+use std::array;
+use std::collections::{hash_map, HashMap};
+use std::hash::BuildHasher;
 
-pub struct HashMapComponentStorage<T: Component> {
-    components: HashMap<usize, T>,
+/// an implementation of `ComponentStorage` that uses a hash map as backing memory, hashing
+/// indices (entity IDs) with `S` (a fast, deterministic integer hasher by default)
+pub struct HashMapComponentStorage<T: Component, S: BuildHasher = BuildIndexHasher> {
+    components: HashMap<usize, T, S>,
 }
 
 impl<T> HashMapComponentStorage<T>
 where
     T: Component,
 {
+    /// create a new, empty `HashMapComponentStorage` that hashes indices with `BuildIndexHasher`
     pub fn new() -> Self {
         Default::default()
     }
 }
 
-impl<T> ComponentStorage<T> for HashMapComponentStorage<T>
+impl<T, S> HashMapComponentStorage<T, S>
 where
     T: Component,
+    S: BuildHasher,
+{
+    /// create a new `HashMapComponentStorage` that hashes indices (entity IDs) with the given
+    /// hasher builder (e.g. `BuildIndexHasher::with_seed` or std's `RandomState`)
+    pub fn with_hasher(hasher: S) -> Self {
+        Self {
+            components: HashMap::with_hasher(hasher),
+        }
+    }
+
+    /// create a new `HashMapComponentStorage` with room for a certain number of components
+    /// reserved in advance, which hashes indices (entity IDs) with the given hasher builder
+    pub fn with_capacity_and_hasher(capacity: usize, hasher: S) -> Self {
+        Self {
+            components: HashMap::with_capacity_and_hasher(capacity, hasher),
+        }
+    }
+
+    /// get the hasher builder used to hash indices (entity IDs)
+    pub fn hasher(&self) -> &S {
+        self.components.hasher()
+    }
+
+    /// look up exactly `N` distinct indices (entity IDs) at once, writing the results into
+    /// `components`
+    fn get_disjoint_into<'a, const N: usize>(
+        &'a mut self,
+        indices: &[usize],
+        components: &mut [Option<&'a mut T>],
+    ) {
+        let keys: [&usize; N] = array::from_fn(|i| &indices[i]);
+        for (component, found) in components
+            .iter_mut()
+            .zip(self.components.get_disjoint_mut(keys))
+        {
+            *component = found;
+        }
+    }
+}
+
+impl<T, S> ComponentStorage<T> for HashMapComponentStorage<T, S>
+where
+    T: Component,
+    S: BuildHasher,
 {
     fn get(&self, index: usize) -> Option<&T> {
         self.components.get(&index)
     }
 
+    fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        self.components.get_mut(&index)
+    }
+
+    fn get_many_mut_into<'a>(
+        &'a mut self,
+        indices: &[usize],
+        components: &mut [Option<&'a mut T>],
+    ) -> Result<(), GetManyMutError> {
+        clear_and_check_disjoint::<T>(indices, components)?;
+        match indices.len() {
+            0 => {}
+            1 => self.get_disjoint_into::<1>(indices, components),
+            2 => self.get_disjoint_into::<2>(indices, components),
+            3 => self.get_disjoint_into::<3>(indices, components),
+            4 => self.get_disjoint_into::<4>(indices, components),
+            // looking up more indices at once than that isn't common enough to be worth
+            // generating a lookup for, so visit every component instead
+            _ => get_many_from_iter(self.iter_mut(), indices, components),
+        }
+        Ok(())
+    }
+
     fn insert(&mut self, index: usize, component: T) -> Result<Option<T>, ComponentWriteError> {
         Ok(self.components.insert(index, component))
     }
@@ -30,11 +106,80 @@ where
     fn delete(&mut self, index: usize) -> Result<Option<T>, ComponentWriteError> {
         Ok(self.components.remove(&index))
     }
+
+    fn entry(&mut self, index: usize) -> Result<Entry<'_, T>, ComponentWriteError> {
+        Ok(match self.components.entry(index) {
+            hash_map::Entry::Occupied(entry) => {
+                Entry::Occupied(OccupiedEntry::new(index, entry.into_mut()))
+            }
+            hash_map::Entry::Vacant(entry) => {
+                Entry::Vacant(VacantEntry::with_slot(index, VacantSlot::HashMap(entry)))
+            }
+        })
+    }
+
+    fn iter(&self) -> ComponentIter<'_, T> {
+        Box::new(
+            self.components
+                .iter()
+                .map(|(index, component)| (*index, component)),
+        )
+    }
+
+    fn iter_mut(&mut self) -> ComponentIterMut<'_, T> {
+        Box::new(
+            self.components
+                .iter_mut()
+                .map(|(index, component)| (*index, component)),
+        )
+    }
+
+    fn len(&self) -> usize {
+        self.components.len()
+    }
+
+    fn contains(&self, index: usize) -> bool {
+        self.components.contains_key(&index)
+    }
+
+    fn clear(&mut self) {
+        self.components.clear();
+    }
+
+    fn capacity(&self) -> usize {
+        self.components.capacity()
+    }
+
+    fn reserve(&mut self, additional: usize) {
+        self.components.reserve(additional);
+    }
+
+    fn shrink_to_fit(&mut self) {
+        self.components.shrink_to_fit();
+    }
+
+    fn memory_usage(&self) -> MemoryUsage {
+        MemoryUsage::new::<Self>(hash_map_heap_bytes::<usize, T>(self.components.capacity()))
+    }
+
+    fn extend(
+        &mut self,
+        components: &mut dyn Iterator<Item = (usize, T)>,
+    ) -> Result<(), ComponentWriteError> {
+        self.components.extend(components);
+        Ok(())
+    }
+
+    fn retain(&mut self, predicate: &mut dyn FnMut(usize, &mut T) -> bool) {
+        self.components
+            .retain(|index, component| predicate(*index, component));
+    }
 }
 
-impl<T> Default for HashMapComponentStorage<T>
+impl<T, S> Default for HashMapComponentStorage<T, S>
 where
     T: Component,
+    S: BuildHasher + Default,
 {
     fn default() -> Self {
         Self {
@@ -46,22 +191,23 @@ where
 #[cfg(test)]
 mod test {
     use crate::component::hash_map_component_storage::HashMapComponentStorage;
-    use crate::component::test::{
-        test_init_behavior, test_insert_and_update_behavior, TestComponent,
-    };
+    use crate::component::index_hasher::BuildIndexHasher;
+    use crate::component::memory_usage::hash_map_heap_bytes;
+    use crate::component::testing::{test_conformance, TestComponent};
     use crate::component::ComponentStorage;
+    use std::collections::hash_map::RandomState;
 
     #[test]
-    fn test_init() {
-        let storage: HashMapComponentStorage<TestComponent> = HashMapComponentStorage::default();
-        test_init_behavior(&storage, 0..64);
-    }
-
-    #[test]
-    fn test_insert_update() {
-        let mut storage: HashMapComponentStorage<TestComponent> =
-            HashMapComponentStorage::default();
-        test_insert_and_update_behavior(&mut storage, 0..64);
+    fn test_conformance_suite() {
+        test_conformance(HashMapComponentStorage::<TestComponent>::default, None);
+        test_conformance(
+            || HashMapComponentStorage::with_hasher(BuildIndexHasher::with_seed(0xC0FFEE)),
+            None,
+        );
+        test_conformance(
+            || HashMapComponentStorage::with_hasher(RandomState::new()),
+            None,
+        );
     }
 
     #[test]
@@ -97,4 +243,36 @@ mod test {
             ),
         }
     }
+
+    #[test]
+    fn test_seeded_iteration_is_deterministic() {
+        let fill = || {
+            let mut storage = HashMapComponentStorage::with_hasher(BuildIndexHasher::with_seed(42));
+            for i in (0..500).step_by(7) {
+                assert!(storage.insert(i, TestComponent(i as i32)).is_ok());
+            }
+            storage
+                .iter()
+                .map(|(index, _)| index)
+                .collect::<Vec<usize>>()
+        };
+        assert_eq!(fill(), fill(), "iteration order differed between storages");
+    }
+
+    #[test]
+    fn test_memory_usage() {
+        let mut storage: HashMapComponentStorage<TestComponent> =
+            HashMapComponentStorage::default();
+        assert_eq!(storage.memory_usage().heap_bytes, 0);
+        for index in 0..100 {
+            let _ = storage.insert(index, TestComponent(1));
+        }
+        assert_eq!(
+            storage.memory_usage().heap_bytes,
+            hash_map_heap_bytes::<usize, TestComponent>(storage.capacity())
+        );
+        storage.clear();
+        storage.shrink_to_fit();
+        assert_eq!(storage.memory_usage().heap_bytes, 0);
+    }
 }
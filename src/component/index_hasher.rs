@@ -0,0 +1,142 @@
+//! a fast, deterministic hasher for integer keys such as indices (entity IDs)
+
+use std::hash::{BuildHasher, Hasher};
+
+/// the multiplier used to mix each word into the hash (the same odd constant FxHash uses)
+const MULTIPLIER: u64 = 0x51_7c_c1_b7_27_22_0a_95;
+
+/// a `Hasher` that mixes integers with a rotate, an xor and a multiply, which is much faster
+/// than SipHash for integer keys (it isn't resistant to deliberately colliding keys, so it
+/// shouldn't be used for keys chosen by an untrusted party)
+#[derive(Clone, Copy, Debug)]
+pub struct IndexHasher {
+    hash: u64,
+}
+
+impl IndexHasher {
+    /// create a new `IndexHasher` starting from a seed
+    pub fn new(seed: u64) -> Self {
+        Self { hash: seed }
+    }
+
+    fn add_word(&mut self, word: u64) {
+        self.hash = (self.hash.rotate_left(5) ^ word).wrapping_mul(MULTIPLIER);
+    }
+}
+
+impl Hasher for IndexHasher {
+    fn finish(&self) -> u64 {
+        // hash tables pick buckets from the low bits of the hash, which only depend on the low
+        // bits of the key after a multiply, so fold the well-mixed high half into them
+        self.hash ^ (self.hash >> 32)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        let mut chunks = bytes.chunks_exact(8);
+        for chunk in &mut chunks {
+            let mut word = [0; 8];
+            word.copy_from_slice(chunk);
+            self.add_word(u64::from_le_bytes(word));
+        }
+        let remainder = chunks.remainder();
+        if !remainder.is_empty() {
+            let mut word = [0; 8];
+            word[..remainder.len()].copy_from_slice(remainder);
+            self.add_word(u64::from_le_bytes(word));
+        }
+    }
+
+    fn write_u8(&mut self, i: u8) {
+        self.add_word(i as u64);
+    }
+
+    fn write_u16(&mut self, i: u16) {
+        self.add_word(i as u64);
+    }
+
+    fn write_u32(&mut self, i: u32) {
+        self.add_word(i as u64);
+    }
+
+    fn write_u64(&mut self, i: u64) {
+        self.add_word(i);
+    }
+
+    fn write_usize(&mut self, i: usize) {
+        self.add_word(i as u64);
+    }
+}
+
+/// a `BuildHasher` that creates `IndexHasher`s from a fixed seed, so that hashes (and the
+/// iteration order of hash maps using it) are the same between runs
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct BuildIndexHasher {
+    seed: u64,
+}
+
+impl BuildIndexHasher {
+    /// create a new `BuildIndexHasher` with the default seed of zero
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// create a new `BuildIndexHasher` with a seed other than the default of zero
+    pub fn with_seed(seed: u64) -> Self {
+        Self { seed }
+    }
+
+    /// get the seed that new hashers start from
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+}
+
+impl BuildHasher for BuildIndexHasher {
+    type Hasher = IndexHasher;
+
+    fn build_hasher(&self) -> Self::Hasher {
+        IndexHasher::new(self.seed)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::component::index_hasher::BuildIndexHasher;
+    use std::collections::HashSet;
+    use std::hash::BuildHasher;
+
+    #[test]
+    fn test_deterministic() {
+        let a = BuildIndexHasher::with_seed(7);
+        let b = BuildIndexHasher::with_seed(7);
+        for i in 0..1000usize {
+            assert_eq!(a.hash_one(i), b.hash_one(i), "hash of {} differed", i);
+        }
+        assert_ne!(
+            BuildIndexHasher::new().hash_one(1usize),
+            a.hash_one(1usize),
+            "seed did not change the hash"
+        );
+    }
+
+    #[test]
+    fn test_sequential_indices_spread() {
+        let hasher = BuildIndexHasher::new();
+        let hashes: HashSet<u64> = (0..10000usize).map(|i| hasher.hash_one(i)).collect();
+        assert_eq!(hashes.len(), 10000, "sequential indices collided");
+        // hash tables use the top 7 bits to filter candidate slots
+        let top_bits: HashSet<u64> = (0..1024usize).map(|i| hasher.hash_one(i) >> 57).collect();
+        assert!(
+            top_bits.len() > 64,
+            "top bits of sequential indices were poorly distributed ({} distinct values)",
+            top_bits.len()
+        );
+    }
+
+    #[test]
+    fn test_bytes_and_words_agree() {
+        let hasher = BuildIndexHasher::new();
+        assert_eq!(hasher.hash_one(42u64), hasher.hash_one(42usize));
+        assert_ne!(hasher.hash_one("ab"), hasher.hash_one("ba"));
+    }
+}
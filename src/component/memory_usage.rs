@@ -0,0 +1,155 @@
+//! reporting how much memory storages use, and estimating it for std collections
+
+use std::iter::Sum;
+use std::mem::{align_of, size_of};
+use std::ops::{Add, AddAssign};
+
+/// the memory used by a storage (or a set of storages)
+///
+/// memory owned by the stored values themselves (e.g. the contents of a `String` field) isn't
+/// counted
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct MemoryUsage {
+    /// bytes taken up by the storage value itself (including any fixed-size arrays it holds)
+    pub inline_bytes: usize,
+    /// bytes allocated on the heap (including capacity that isn't in use yet)
+    pub heap_bytes: usize,
+    /// bytes of files mapped into memory
+    pub mapped_bytes: usize,
+}
+
+impl MemoryUsage {
+    /// create a new `MemoryUsage` for a value of type `S` that owns some heap memory
+    pub fn new<S>(heap_bytes: usize) -> Self {
+        Self {
+            inline_bytes: size_of::<S>(),
+            heap_bytes,
+            mapped_bytes: 0,
+        }
+    }
+
+    /// get the total number of bytes used
+    pub fn total(&self) -> usize {
+        self.inline_bytes + self.heap_bytes + self.mapped_bytes
+    }
+
+    /// count this memory as heap memory owned by something else (e.g. a storage that is boxed
+    /// or kept inside a collection)
+    pub fn into_heap(self) -> Self {
+        Self {
+            inline_bytes: 0,
+            heap_bytes: self.inline_bytes + self.heap_bytes,
+            mapped_bytes: self.mapped_bytes,
+        }
+    }
+}
+
+impl Add for MemoryUsage {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self {
+            inline_bytes: self.inline_bytes + other.inline_bytes,
+            heap_bytes: self.heap_bytes + other.heap_bytes,
+            mapped_bytes: self.mapped_bytes + other.mapped_bytes,
+        }
+    }
+}
+
+impl AddAssign for MemoryUsage {
+    fn add_assign(&mut self, other: Self) {
+        *self = *self + other;
+    }
+}
+
+impl Sum for MemoryUsage {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(Self::default(), Add::add)
+    }
+}
+
+/// get the heap memory allocated by a `Vec<T>` with a certain capacity
+pub fn vec_heap_bytes<T>(capacity: usize) -> usize {
+    capacity * size_of::<T>()
+}
+
+/// estimate the heap memory allocated by a `HashMap<K, V>` with a certain capacity
+///
+/// std doesn't report how much a map allocates, so this follows the current layout of its
+/// SwissTable (a bucket array and one control byte per bucket, plus a group of trailing control
+/// bytes). the result is an estimate, and may drift if std changes its layout
+pub fn hash_map_heap_bytes<K, V>(capacity: usize) -> usize {
+    // std's SwissTable scans control bytes a group at a time: an SSE2 register's worth where
+    // SSE2 is available, and 8 bytes (a NEON register or a machine word) everywhere else
+    #[cfg(target_feature = "sse2")]
+    const GROUP_WIDTH: usize = 16;
+    #[cfg(not(target_feature = "sse2"))]
+    const GROUP_WIDTH: usize = 8;
+    if capacity == 0 {
+        return 0; // empty maps don't allocate
+    }
+    // the inverse of the map's growth policy, which keeps small tables full and larger tables
+    // at most 7/8 full
+    let buckets = if capacity < 8 {
+        capacity + 1
+    } else {
+        capacity / 7 * 8
+    };
+    let bucket_bytes =
+        (buckets * size_of::<(K, V)>()).next_multiple_of(align_of::<(K, V)>().max(GROUP_WIDTH));
+    bucket_bytes + buckets + GROUP_WIDTH
+}
+
+/// estimate the heap memory allocated by a `BTreeMap<K, V>` holding `len` entries
+///
+/// std doesn't expose how many nodes a map has, so this assumes the keys were inserted in
+/// ascending order (as entity IDs usually are), which leaves every node but the last on each
+/// level a little over half full. maps filled in random order pack their nodes more tightly and
+/// use somewhat less, so the result is only an estimate
+pub fn b_tree_map_heap_bytes<K, V>(len: usize) -> usize {
+    const CAPACITY: usize = 11; // the most entries a node can hold
+    if len == 0 {
+        return 0; // empty maps don't allocate
+    }
+    // when a full node is split at its right edge, 6 entries stay behind and 1 moves up to
+    // the parent, so every node but the last on a level accounts for 7 entries
+    let nodes = |entries: usize| {
+        if entries <= CAPACITY {
+            1
+        } else {
+            (entries - 5) / 7 + 1
+        }
+    };
+    let align = align_of::<K>()
+        .max(align_of::<V>())
+        .max(align_of::<usize>());
+    // a parent pointer, the index in the parent, the length, and the keys and values
+    let leaf_size = (size_of::<usize>() + 4 + CAPACITY * (size_of::<K>() + size_of::<V>()))
+        .next_multiple_of(align);
+    // a leaf node plus a pointer to each child
+    let internal_size = leaf_size + (CAPACITY + 1) * size_of::<usize>();
+    let leaves = nodes(len);
+    let mut internal_nodes = 0;
+    let mut level = leaves;
+    while level > 1 {
+        // each node on the level above separates its children with one fewer entry
+        level = nodes(level - 1);
+        internal_nodes += level;
+    }
+    leaves * leaf_size + internal_nodes * internal_size
+}
+
+#[cfg(test)]
+mod test {
+    use crate::component::memory_usage::MemoryUsage;
+
+    #[test]
+    fn test_sum() {
+        let usages = [MemoryUsage::new::<u64>(10), MemoryUsage::new::<u32>(5)];
+        let total: MemoryUsage = usages.into_iter().sum();
+        assert_eq!(total.inline_bytes, 12);
+        assert_eq!(total.heap_bytes, 15);
+        assert_eq!(total.total(), 27);
+        assert_eq!(total.into_heap().heap_bytes, 27);
+    }
+}
@@ -0,0 +1,731 @@
+//! a `ComponentStorage` backed by a memory-mapped file, for plain-old-data components that are
+//! too large to keep in memory or that should persist between runs (enabled by the `mmap`
+//! feature)
+
+use crate::component::disjoint::get_many_slots_mut;
+use crate::component::entry::{Entry, InsertVacant, OccupiedEntry, VacantEntry};
+use crate::component::error::{ComponentWriteError, ComponentWriteErrorKind, GetManyMutError};
+use crate::component::memory_usage::MemoryUsage;
+use crate::component::{Component, ComponentIter, ComponentIterMut, ComponentStorage};
+use memmap2::{MmapMut, MmapOptions};
+use std::fmt::{Debug, Formatter};
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::marker::PhantomData;
+use std::mem::{align_of, size_of};
+use std::path::Path;
+use std::{ptr, slice};
+
+/// a type that can be stored as raw bytes and read back from any bytes of the same size
+///
+/// # Safety
+/// every bit pattern of `size_of::<Self>()` bytes must be a valid value of the type (so it
+/// can't contain references, pointers, `bool`s, `char`s or enums), since the bytes in a file
+/// can be anything. types should also be `#[repr(C)]` or `#[repr(transparent)]`, so that their
+/// layout doesn't change between builds that share a file
+pub unsafe trait PlainOldData: Copy + 'static {}
+
+macro_rules! impl_plain_old_data {
+    ($($t:ty),+) => {
+        $(
+            // SAFETY: every bit pattern is a valid value of a primitive integer or float
+            unsafe impl PlainOldData for $t {}
+        )+
+    };
+}
+
+impl_plain_old_data!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize, f32, f64);
+
+// SAFETY: an array of plain old data is laid out without padding between its elements
+unsafe impl<T: PlainOldData, const N: usize> PlainOldData for [T; N] {}
+
+/// the bytes at the start of every storage file
+const MAGIC: [u8; 8] = *b"WYRDMMAP";
+
+/// the version of the file layout
+const VERSION: u32 = 1;
+
+/// the number of slots a new file is created with, when no capacity is given
+const INITIAL_CAPACITY: usize = 64;
+
+/// the header at the start of every storage file (stored in native byte order, so files can't
+/// be shared between machines with different endianness)
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct Header {
+    magic: [u8; 8],
+    version: u32,
+    slot_size: u32,
+    value_size: u32,
+    value_align: u32,
+    slot_count: u64,
+}
+
+/// a slot in the file, holding a component if `occupied` isn't zero
+///
+/// the padding bytes after `occupied` (and after `value`, if any) are copied into the file along
+/// with the fields, so they can hold whatever bytes were in memory when the slot was written
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct Slot<T> {
+    occupied: u8,
+    value: T,
+}
+
+/// an implementation of `ComponentStorage` that keeps its components in a memory-mapped file,
+/// laid out as a header followed by one slot (an occupied flag and a component) per index
+/// (entity ID)
+///
+/// the file grows (and is remapped) whenever a component is stored past its end. changes are
+/// written back to the file by the operating system in its own time, or immediately by `flush`
+/// (which also happens when the storage is dropped)
+///
+/// slots are written to the file byte for byte, including the padding between the occupied flag
+/// and the component, so the file can contain leftover bytes from the process's memory. don't
+/// share storage files if that matters
+pub struct MmapComponentStorage<T: Component + PlainOldData> {
+    file: File,
+    map: MmapMut,
+    slot_count: usize,
+    len: usize,
+    component: PhantomData<T>,
+}
+
+impl<T> MmapComponentStorage<T>
+where
+    T: Component + PlainOldData,
+{
+    /// the offset of the first slot in the file (the header, padded to the slot alignment)
+    const DATA_OFFSET: usize = size_of::<Header>().next_multiple_of(align_of::<Slot<T>>());
+
+    /// create a new, empty storage file at a path (replacing any file already there)
+    ///
+    /// # Safety
+    /// the file must not be modified by anything else (including other processes) while it's
+    /// mapped by the storage
+    pub unsafe fn create<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        // SAFETY: upheld by the caller
+        unsafe { Self::create_with_capacity(path, INITIAL_CAPACITY) }
+    }
+
+    /// create a new, empty storage file at a path (replacing any file already there), with
+    /// room for indices (entity IDs) below `capacity` allocated in advance
+    ///
+    /// # Safety
+    /// the file must not be modified by anything else (including other processes) while it's
+    /// mapped by the storage
+    pub unsafe fn create_with_capacity<P: AsRef<Path>>(
+        path: P,
+        capacity: usize,
+    ) -> io::Result<Self> {
+        const {
+            assert!(
+                align_of::<Slot<T>>() <= 4096,
+                "MmapComponentStorage requires components aligned to at most 4096 bytes"
+            )
+        };
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)?;
+        file.set_len(Self::file_size(capacity)?)?;
+        // SAFETY: upheld by the caller
+        let map = unsafe { MmapMut::map_mut(&file)? };
+        let mut storage = Self {
+            file,
+            map,
+            slot_count: capacity,
+            len: 0,
+            component: PhantomData,
+        };
+        storage.write_header();
+        Ok(storage)
+    }
+
+    /// open an existing storage file at a path, with its components intact
+    ///
+    /// fails with `io::ErrorKind::InvalidData` if the file isn't a storage file for components
+    /// of the same size and alignment as `T`
+    ///
+    /// # Safety
+    /// the file must not be modified by anything else (including other processes) while it's
+    /// mapped by the storage
+    pub unsafe fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        const {
+            assert!(
+                align_of::<Slot<T>>() <= 4096,
+                "MmapComponentStorage requires components aligned to at most 4096 bytes"
+            )
+        };
+        let file = OpenOptions::new().read(true).write(true).open(path)?;
+        let file_size = file.metadata()?.len();
+        if file_size < size_of::<Header>() as u64 {
+            return Err(invalid_data("file is too short to be a component storage"));
+        }
+        // SAFETY: upheld by the caller
+        let map = unsafe { MmapMut::map_mut(&file)? };
+        // SAFETY: the map is at least as long as the header, and every field of the header is
+        // plain old data
+        let header = unsafe { ptr::read_unaligned(map.as_ptr().cast::<Header>()) };
+        if header.magic != MAGIC {
+            return Err(invalid_data("file is not a component storage"));
+        }
+        if header.version != VERSION {
+            return Err(invalid_data(
+                "component storage file has an unsupported version",
+            ));
+        }
+        if header.slot_size as usize != size_of::<Slot<T>>()
+            || header.value_size as usize != size_of::<T>()
+            || header.value_align as usize != align_of::<T>()
+        {
+            return Err(invalid_data(
+                "component storage file holds a different component type",
+            ));
+        }
+        let slot_count = usize::try_from(header.slot_count)
+            .map_err(|_| invalid_data("component storage file is too large"))?;
+        if Self::file_size(slot_count)? != file_size {
+            return Err(invalid_data(
+                "component storage file length doesn't match its header",
+            ));
+        }
+        let mut storage = Self {
+            file,
+            map,
+            slot_count,
+            len: 0,
+            component: PhantomData,
+        };
+        storage.len = storage
+            .slots()
+            .iter()
+            .filter(|slot| slot.occupied != 0)
+            .count();
+        Ok(storage)
+    }
+
+    /// write every change to the file now, rather than whenever the operating system does
+    pub fn flush(&self) -> io::Result<()> {
+        self.map.flush()
+    }
+
+    /// get the size of a file with a certain number of slots
+    fn file_size(slot_count: usize) -> io::Result<u64> {
+        slot_count
+            .checked_mul(size_of::<Slot<T>>())
+            .and_then(|size| size.checked_add(Self::DATA_OFFSET))
+            .map(|size| size as u64)
+            .ok_or_else(|| io::Error::other("component storage file would be too large"))
+    }
+
+    fn write_header(&mut self) {
+        let header = Header {
+            magic: MAGIC,
+            version: VERSION,
+            slot_size: size_of::<Slot<T>>() as u32,
+            value_size: size_of::<T>() as u32,
+            value_align: align_of::<T>() as u32,
+            slot_count: self.slot_count as u64,
+        };
+        // SAFETY: the map is always at least as long as the header
+        unsafe { ptr::write_unaligned(self.map.as_mut_ptr().cast::<Header>(), header) };
+    }
+
+    fn slots(&self) -> &[Slot<T>] {
+        // SAFETY: the map always holds `slot_count` slots after the header, starting at an
+        // offset aligned for `Slot<T>` (the map itself is page-aligned), and any bytes are a
+        // valid `Slot<T>` since both of its fields are plain old data
+        unsafe {
+            slice::from_raw_parts(
+                self.map.as_ptr().add(Self::DATA_OFFSET).cast::<Slot<T>>(),
+                self.slot_count,
+            )
+        }
+    }
+
+    fn slots_mut(&mut self) -> &mut [Slot<T>] {
+        // SAFETY: as in `slots`, and the map is borrowed mutably
+        unsafe {
+            slice::from_raw_parts_mut(
+                self.map
+                    .as_mut_ptr()
+                    .add(Self::DATA_OFFSET)
+                    .cast::<Slot<T>>(),
+                self.slot_count,
+            )
+        }
+    }
+
+    /// grow (or shrink) the file to hold a certain number of slots, and remap it
+    ///
+    /// the map is never left longer than the file (reading past the end of a mapped file
+    /// crashes the process), so the file is grown before it's remapped, and remapped before
+    /// it's shrunk. if anything fails, the storage is left holding the slots it could map
+    fn resize(&mut self, slot_count: usize) -> io::Result<()> {
+        let old_size = Self::file_size(self.slot_count)?;
+        let new_size = Self::file_size(slot_count)?;
+        if new_size >= old_size {
+            self.file.set_len(new_size)?;
+            // SAFETY: the caller of the constructor promised that nothing else modifies the
+            // file, and the old map (which is replaced) isn't borrowed since `self` is borrowed
+            // mutably
+            match unsafe { MmapMut::map_mut(&self.file) } {
+                Ok(map) => self.map = map,
+                Err(err) => {
+                    // the old map still fits in the file, but put the file back to the length
+                    // its header gives
+                    let _ = self.file.set_len(old_size);
+                    return Err(err);
+                }
+            }
+        } else {
+            // map only the part of the file that will be kept, so the map already fits once
+            // the file is shrunk
+            // SAFETY: as above
+            self.map = unsafe {
+                MmapOptions::new()
+                    .len(new_size as usize)
+                    .map_mut(&self.file)?
+            };
+            self.slot_count = slot_count;
+            // if shrinking the file fails, the header still gives its old length (the slots
+            // past the new end are all empty), so it can be reopened as it was
+            self.file.set_len(new_size)?;
+        }
+        self.slot_count = slot_count;
+        self.write_header();
+        Ok(())
+    }
+
+    /// make sure a slot exists for an index (entity ID), growing the file if necessary
+    fn ensure_slot(&mut self, index: usize) -> Result<(), ComponentWriteError> {
+        if index < self.slot_count {
+            return Ok(());
+        }
+        let slot_count = index
+            .checked_add(1)
+            .map(|min| min.max(self.slot_count.saturating_mul(2)))
+            .ok_or_else(|| {
+                ComponentWriteError::new_with_kind::<T>(
+                    index,
+                    ComponentWriteErrorKind::CapacityExceeded,
+                )
+            })?;
+        self.resize(slot_count).map_err(|err| {
+            ComponentWriteError::new_with_kind::<T>(
+                index,
+                ComponentWriteErrorKind::CapacityExceeded,
+            )
+            .with_detail("failed to grow component storage file")
+            .with_source(err)
+        })
+    }
+}
+
+fn invalid_data(detail: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, detail)
+}
+
+impl<T> ComponentStorage<T> for MmapComponentStorage<T>
+where
+    T: Component + PlainOldData,
+{
+    fn get(&self, index: usize) -> Option<&T> {
+        match self.slots().get(index) {
+            Some(slot) if slot.occupied != 0 => Some(&slot.value),
+            _ => None,
+        }
+    }
+
+    fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        match self.slots_mut().get_mut(index) {
+            Some(slot) if slot.occupied != 0 => Some(&mut slot.value),
+            _ => None,
+        }
+    }
+
+    fn get_many_mut_into<'a>(
+        &'a mut self,
+        indices: &[usize],
+        components: &mut [Option<&'a mut T>],
+    ) -> Result<(), GetManyMutError> {
+        // SAFETY: each index is its own slot
+        unsafe {
+            get_many_slots_mut(self.slots_mut(), indices, components, Some, |slot| {
+                (slot.occupied != 0).then_some(&mut slot.value)
+            })
+        }
+    }
+
+    unsafe fn get_unchecked(&self, index: usize) -> &T {
+        // SAFETY: the caller guarantees the slot is occupied, so it's within the map
+        unsafe { &self.slots().get_unchecked(index).value }
+    }
+
+    unsafe fn get_unchecked_mut(&mut self, index: usize) -> &mut T {
+        // SAFETY: the caller guarantees the slot is occupied, so it's within the map
+        unsafe { &mut self.slots_mut().get_unchecked_mut(index).value }
+    }
+
+    fn insert(&mut self, index: usize, component: T) -> Result<Option<T>, ComponentWriteError> {
+        self.ensure_slot(index)?;
+        let slot = &mut self.slots_mut()[index];
+        let previous = (slot.occupied != 0).then_some(slot.value);
+        *slot = Slot {
+            occupied: 1,
+            value: component,
+        };
+        if previous.is_none() {
+            self.len += 1;
+        }
+        Ok(previous)
+    }
+
+    fn delete(&mut self, index: usize) -> Result<Option<T>, ComponentWriteError> {
+        match self.slots_mut().get_mut(index) {
+            Some(slot) if slot.occupied != 0 => {
+                slot.occupied = 0;
+                let component = slot.value;
+                self.len -= 1;
+                Ok(Some(component))
+            }
+            _ => Ok(None), // nothing stored at this index, so there's nothing to delete
+        }
+    }
+
+    fn entry(&mut self, index: usize) -> Result<Entry<'_, T>, ComponentWriteError> {
+        // grow first, since storing through a vacant entry can't fail
+        self.ensure_slot(index)?;
+        if self.slots()[index].occupied != 0 {
+            return Ok(Entry::Occupied(OccupiedEntry::new(
+                index,
+                &mut self.slots_mut()[index].value,
+            )));
+        }
+        Ok(Entry::Vacant(VacantEntry::new(index, self)))
+    }
+
+    fn iter(&self) -> ComponentIter<'_, T> {
+        Box::new(
+            self.slots()
+                .iter()
+                .enumerate()
+                .filter(|(_, slot)| slot.occupied != 0)
+                .map(|(index, slot)| (index, &slot.value)),
+        )
+    }
+
+    fn iter_mut(&mut self) -> ComponentIterMut<'_, T> {
+        Box::new(
+            self.slots_mut()
+                .iter_mut()
+                .enumerate()
+                .filter(|(_, slot)| slot.occupied != 0)
+                .map(|(index, slot)| (index, &mut slot.value)),
+        )
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn clear(&mut self) {
+        for slot in self.slots_mut() {
+            slot.occupied = 0;
+        }
+        self.len = 0;
+    }
+
+    fn capacity(&self) -> usize {
+        self.slot_count
+    }
+
+    fn reserve(&mut self, additional: usize) {
+        let used_length = self
+            .slots()
+            .iter()
+            .rposition(|slot| slot.occupied != 0)
+            .map_or(0, |last| last + 1);
+        if let Some(slot_count) = used_length.checked_add(additional) {
+            if slot_count > self.slot_count {
+                // reserving is only a hint, so a failure is left for the next insert to report
+                // (`resize` leaves the storage usable if it fails)
+                let _ = self.resize(slot_count);
+            }
+        }
+    }
+
+    fn shrink_to_fit(&mut self) {
+        let used_length = self
+            .slots()
+            .iter()
+            .rposition(|slot| slot.occupied != 0)
+            .map_or(0, |last| last + 1);
+        if used_length < self.slot_count {
+            // shrinking is only a hint, so a failure just leaves the file larger (`resize`
+            // leaves the storage usable if it fails)
+            let _ = self.resize(used_length);
+        }
+    }
+
+    fn memory_usage(&self) -> MemoryUsage {
+        MemoryUsage {
+            mapped_bytes: self.map.len(),
+            ..MemoryUsage::new::<Self>(0)
+        }
+    }
+
+    fn retain(&mut self, predicate: &mut dyn FnMut(usize, &mut T) -> bool) {
+        let mut removed = 0;
+        for (index, slot) in self.slots_mut().iter_mut().enumerate() {
+            if slot.occupied != 0 && !predicate(index, &mut slot.value) {
+                slot.occupied = 0;
+                removed += 1;
+            }
+        }
+        self.len -= removed;
+    }
+}
+
+impl<T> InsertVacant<T> for MmapComponentStorage<T>
+where
+    T: Component + PlainOldData,
+{
+    fn insert_vacant(&mut self, index: usize, component: T) -> &mut T {
+        // `entry` already grew the file to hold the slot
+        self.len += 1;
+        let slot = &mut self.slots_mut()[index];
+        *slot = Slot {
+            occupied: 1,
+            value: component,
+        };
+        &mut slot.value
+    }
+}
+
+impl<T> Debug for MmapComponentStorage<T>
+where
+    T: Component + PlainOldData,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<T> Drop for MmapComponentStorage<T>
+where
+    T: Component + PlainOldData,
+{
+    fn drop(&mut self) {
+        // errors can't be reported from here; call `flush` first to see them
+        let _ = self.map.flush();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::component::error::ComponentWriteErrorKind;
+    use crate::component::mmap_component_storage::{MmapComponentStorage, PlainOldData};
+    use crate::component::testing::{test_conformance, TestComponent};
+    use crate::component::{Component, ComponentStorage};
+    use std::cell::RefCell;
+    use std::fs;
+    use std::io;
+    use std::path::PathBuf;
+    use std::process;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    // SAFETY: the only field of `TestComponent` is an `i32`, so every bit pattern is valid. its
+    // layout isn't fixed, but it only has to match within one test run (for the conformance
+    // suite, which needs `TestComponent`)
+    unsafe impl PlainOldData for TestComponent {}
+
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    #[repr(transparent)]
+    struct Sample(i32);
+
+    impl Component for Sample {}
+
+    // SAFETY: `Sample` is a transparent wrapper around an `i32`
+    unsafe impl PlainOldData for Sample {}
+
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    #[repr(C)]
+    struct Chunk {
+        height: [f32; 4],
+        seed: u64,
+    }
+
+    impl Component for Chunk {}
+
+    // SAFETY: `Chunk` is `#[repr(C)]` and made of floats and integers, with no padding
+    unsafe impl PlainOldData for Chunk {}
+
+    /// a path in the temporary directory that is deleted when dropped
+    struct TempPath(PathBuf);
+
+    impl TempPath {
+        fn new() -> Self {
+            static NEXT: AtomicUsize = AtomicUsize::new(0);
+            let name = format!(
+                "wyrd-mmap-test-{}-{}",
+                process::id(),
+                NEXT.fetch_add(1, Ordering::Relaxed)
+            );
+            Self(std::env::temp_dir().join(name))
+        }
+    }
+
+    impl Drop for TempPath {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+
+    fn new_storage_at<T: Component + PlainOldData>(path: &TempPath) -> MmapComponentStorage<T> {
+        // SAFETY: the file is only used by this storage
+        match unsafe { MmapComponentStorage::create(&path.0) } {
+            Ok(storage) => storage,
+            Err(err) => panic!("creating a storage file failed: {:?}", err),
+        }
+    }
+
+    #[test]
+    fn test_conformance_suite() {
+        // every file is kept until the suite is done with its storage
+        let paths = RefCell::new(Vec::new());
+        test_conformance(
+            || {
+                let path = TempPath::new();
+                let storage = new_storage_at::<TestComponent>(&path);
+                paths.borrow_mut().push(path);
+                storage
+            },
+            None,
+        );
+    }
+
+    #[test]
+    fn test_grows_file() {
+        let path = TempPath::new();
+        // SAFETY: the file is only used by this storage
+        let mut storage: MmapComponentStorage<Sample> =
+            match unsafe { MmapComponentStorage::create_with_capacity(&path.0, 4) } {
+                Ok(storage) => storage,
+                Err(err) => panic!("creating a storage file failed: {:?}", err),
+            };
+        assert_eq!(storage.capacity(), 4);
+        let initial_size = fs::metadata(&path.0).map(|m| m.len()).unwrap_or(0);
+        assert!(storage.insert(1, Sample(1)).is_ok());
+        assert!(storage.insert(1000, Sample(1000)).is_ok());
+        assert!(storage.capacity() > 1000, "storage did not grow");
+        let grown_size = fs::metadata(&path.0).map(|m| m.len()).unwrap_or(0);
+        assert!(grown_size > initial_size, "file did not grow");
+        assert_eq!(storage.get(1), Some(&Sample(1)));
+        assert_eq!(storage.get(1000), Some(&Sample(1000)));
+        assert!(storage.delete(1000).is_ok());
+        storage.shrink_to_fit();
+        assert_eq!(
+            storage.capacity(),
+            2,
+            "storage was not shrunk to its last component"
+        );
+        let shrunk_size = fs::metadata(&path.0).map(|m| m.len()).unwrap_or(0);
+        assert_eq!(
+            storage.memory_usage().mapped_bytes as u64,
+            shrunk_size,
+            "the map and the file were different lengths after shrinking"
+        );
+        assert_eq!(storage.get(1), Some(&Sample(1)));
+        drop(storage);
+        // SAFETY: the file is only used by this storage
+        match unsafe { MmapComponentStorage::<Sample>::open(&path.0) } {
+            Ok(storage) => assert_eq!(storage.get(1), Some(&Sample(1))),
+            Err(err) => panic!("reopening a shrunk storage file failed: {:?}", err),
+        }
+    }
+
+    #[test]
+    fn test_reopen() {
+        let path = TempPath::new();
+        {
+            // SAFETY: the file is only used by this storage
+            let mut storage: MmapComponentStorage<Chunk> =
+                match unsafe { MmapComponentStorage::create(&path.0) } {
+                    Ok(storage) => storage,
+                    Err(err) => panic!("creating a storage file failed: {:?}", err),
+                };
+            for i in [0, 7, 300] {
+                let chunk = Chunk {
+                    height: [i as f32; 4],
+                    seed: i as u64,
+                };
+                assert!(storage.insert(i, chunk).is_ok());
+            }
+            assert!(storage.delete(7).is_ok());
+            if let Err(err) = storage.flush() {
+                panic!("flushing returned error {:?}", err);
+            }
+        }
+        // SAFETY: the file is only used by this storage
+        let storage: MmapComponentStorage<Chunk> =
+            match unsafe { MmapComponentStorage::open(&path.0) } {
+                Ok(storage) => storage,
+                Err(err) => panic!("reopening a storage file failed: {:?}", err),
+            };
+        assert_eq!(storage.len(), 2, "reopened storage had the wrong length");
+        assert_eq!(
+            storage.get(300),
+            Some(&Chunk {
+                height: [300.0; 4],
+                seed: 300
+            }),
+            "component was not intact after reopening"
+        );
+        assert!(storage.get(7).is_none(), "deleted component was reopened");
+    }
+
+    #[test]
+    fn test_reopen_rejects_other_files() {
+        let path = TempPath::new();
+        drop(new_storage_at::<Sample>(&path));
+        // SAFETY: the file is only used by this storage
+        match unsafe { MmapComponentStorage::<Chunk>::open(&path.0) } {
+            Ok(storage) => panic!("opened a file of another component type: {:?}", storage),
+            Err(err) => assert_eq!(err.kind(), io::ErrorKind::InvalidData),
+        }
+        if let Err(err) = fs::write(&path.0, b"not a storage file, but long enough to be one") {
+            panic!("writing test file failed: {:?}", err);
+        }
+        // SAFETY: the file is only used by this storage
+        match unsafe { MmapComponentStorage::<Sample>::open(&path.0) } {
+            Ok(storage) => panic!("opened a file that isn't a storage: {:?}", storage),
+            Err(err) => assert_eq!(err.kind(), io::ErrorKind::InvalidData),
+        }
+    }
+
+    #[test]
+    fn test_err_on_index_too_large() {
+        let path = TempPath::new();
+        let mut storage: MmapComponentStorage<Sample> = new_storage_at(&path);
+        match storage.insert(usize::MAX, Sample(0)) {
+            Ok(x) => panic!("inserting at usize::MAX returned {:?}", x),
+            Err(err) => assert_eq!(err.kind(), ComponentWriteErrorKind::CapacityExceeded),
+        }
+    }
+
+    #[test]
+    fn test_memory_usage() {
+        let path = TempPath::new();
+        let mut storage: MmapComponentStorage<Sample> = new_storage_at(&path);
+        let file_size = |path: &TempPath| fs::metadata(&path.0).map_or(0, |m| m.len() as usize);
+        assert_eq!(storage.memory_usage().mapped_bytes, file_size(&path));
+        assert!(storage.insert(1000, Sample(1000)).is_ok());
+        let usage = storage.memory_usage();
+        assert_eq!(usage.mapped_bytes, file_size(&path));
+        assert_eq!(usage.heap_bytes, 0);
+    }
+}
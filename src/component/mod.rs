@@ -1,111 +1,225 @@
 //! code associated with component types and component storage
 
-use error::ComponentWriteError;
+use disjoint::{clear_and_check_disjoint, get_many_from_iter};
+use entry::Entry;
+use error::{ComponentWriteError, GetManyMutError};
+use memory_usage::{vec_heap_bytes, MemoryUsage};
 use std::fmt::Debug;
+use std::mem::size_of_val;
+use std::ops::Range;
 
 pub mod array_component_storage;
 pub mod b_tree_map_component_storage;
+pub mod bitset;
+pub mod bitset_array_component_storage;
+pub mod bitset_vec_component_storage;
+pub mod concurrent_component_storage;
+mod disjoint;
+pub mod entry;
 pub mod error;
 pub mod hash_map_component_storage;
+pub mod index_hasher;
+pub mod memory_usage;
+#[cfg(feature = "mmap")]
+pub mod mmap_component_storage;
+mod ordered;
+pub mod paged_vec_component_storage;
+pub mod soa_component_storage;
+pub mod sparse_set_component_storage;
+pub mod tag_component_storage;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+pub mod tracked_storage;
 pub mod vec_component_storage;
 
+#[cfg(feature = "derive")]
+pub use wyrd_derive::Component;
+
 /// a marker type representing a type that can be used as a component
 pub trait Component: Sized + Debug {}
 
+/// a component that declares which `ComponentStorage` it is normally stored in, so that it can be
+/// registered without naming a storage (usually implemented with
+/// `#[derive(Component)]` and `#[component(storage = ...)]`)
+pub trait DefaultStorage: Component {
+    /// the storage to use for the component
+    type Storage: ComponentStorage<Self> + Default;
+}
+
+/// an iterator over the components in a `ComponentStorage`, paired with their indices
+pub type ComponentIter<'a, T> = Box<dyn Iterator<Item = (usize, &'a T)> + 'a>;
+
+/// an iterator over mutable references to the components in a `ComponentStorage`,
+/// paired with their indices
+pub type ComponentIterMut<'a, T> = Box<dyn Iterator<Item = (usize, &'a mut T)> + 'a>;
+
 /// type capable of storing a set of different components of the same type
 /// for different entities
 pub trait ComponentStorage<T: Component> {
     /// get a component given the index (entity ID)
     fn get(&self, index: usize) -> Option<&T>;
 
+    /// get a mutable reference to a component given the index (entity ID)
+    fn get_mut(&mut self, index: usize) -> Option<&mut T>;
+
+    /// get mutable references to the components at several different indices (entity IDs) at
+    /// once (e.g. for a system that makes two entities interact), writing the reference for
+    /// each index (or `None` if it has no component) into the same position in `components`
+    ///
+    /// fails without handing out any references if an index appears more than once, and
+    /// panics if `indices` and `components` are different lengths. the default implementation
+    /// visits every stored component, so storages that can find an index directly override it
+    fn get_many_mut_into<'a>(
+        &'a mut self,
+        indices: &[usize],
+        components: &mut [Option<&'a mut T>],
+    ) -> Result<(), GetManyMutError> {
+        clear_and_check_disjoint::<T>(indices, components)?;
+        get_many_from_iter(self.iter_mut(), indices, components);
+        Ok(())
+    }
+
+    /// get mutable references to the components at `N` different indices (entity IDs) at once
+    /// (see `get_many_mut_into`, which also works through a trait object)
+    fn get_many_mut<const N: usize>(
+        &mut self,
+        indices: [usize; N],
+    ) -> Result<[Option<&mut T>; N], GetManyMutError>
+    where
+        Self: Sized,
+    {
+        let mut components = [const { None }; N];
+        self.get_many_mut_into(&indices, &mut components)?;
+        Ok(components)
+    }
+
+    /// get a component given the index (entity ID), skipping the checks `get` makes
+    ///
+    /// # Safety
+    /// a component must be stored at the index
+    unsafe fn get_unchecked(&self, index: usize) -> &T {
+        // SAFETY: the caller guarantees a component is stored at the index
+        unsafe { self.get(index).unwrap_unchecked() }
+    }
+
+    /// get a mutable reference to a component given the index (entity ID), skipping the
+    /// checks `get_mut` makes
+    ///
+    /// # Safety
+    /// a component must be stored at the index
+    unsafe fn get_unchecked_mut(&mut self, index: usize) -> &mut T {
+        // SAFETY: the caller guarantees a component is stored at the index
+        unsafe { self.get_mut(index).unwrap_unchecked() }
+    }
+
     /// store a component given the index (entity ID)
     fn insert(&mut self, index: usize, component: T) -> Result<Option<T>, ComponentWriteError>;
 
     /// delete a component given the index (entity ID)
     fn delete(&mut self, index: usize) -> Result<Option<T>, ComponentWriteError>;
-}
 
-#[cfg(test)]
-pub mod test {
-    use crate::component::{Component, ComponentStorage};
-
-    #[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Ord, PartialOrd)]
-    /// a fake component type for use in unit tests
-    pub struct TestComponent(pub i32);
-
-    impl Component for TestComponent {}
-
-    /// test that a `ComponentStorage` satisfies the expected core initialization behavior
-    pub fn test_init_behavior<S: ComponentStorage<TestComponent>, I: Iterator<Item = usize>>(
-        storage: &S,
-        index_iterator: I,
-    ) {
-        for i in index_iterator {
-            assert!(
-                storage.get(i).is_none(),
-                "element {} in newly initialized ArrayComponentStorage was not None",
-                i
-            )
+    /// get the slot for an index (entity ID) for in-place manipulation, whether or not it
+    /// holds a component (fails if the storage can't hold a component at that index)
+    fn entry(&mut self, index: usize) -> Result<Entry<'_, T>, ComponentWriteError>;
+
+    /// iterate over every stored component along with its index (entity ID)
+    fn iter(&self) -> ComponentIter<'_, T>;
+
+    /// iterate mutably over every stored component along with its index (entity ID)
+    fn iter_mut(&mut self) -> ComponentIterMut<'_, T>;
+
+    /// get the number of components stored
+    fn len(&self) -> usize;
+
+    /// check whether no components are stored
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// check whether a component is stored at the index (entity ID)
+    fn contains(&self, index: usize) -> bool {
+        self.get(index).is_some()
+    }
+
+    /// delete every stored component
+    fn clear(&mut self);
+
+    /// get the number of components that can be stored without allocating more memory
+    /// (for storages addressed directly by index, this is the number of indices covered)
+    fn capacity(&self) -> usize;
+
+    /// reserve memory for at least `additional` more components, where the storage supports it
+    fn reserve(&mut self, additional: usize);
+
+    /// release as much unused memory as possible
+    fn shrink_to_fit(&mut self);
+
+    /// report how much memory the storage is using (by default, estimated as if it kept its
+    /// components in a `Vec` sized to its capacity)
+    fn memory_usage(&self) -> MemoryUsage {
+        MemoryUsage {
+            inline_bytes: size_of_val(self),
+            heap_bytes: vec_heap_bytes::<T>(self.capacity()),
+            mapped_bytes: 0,
         }
     }
 
-    /// test that a `ComponentStorage` satisfies the expected core insertion and updating behavior
-    pub fn test_insert_and_update_behavior<
-        S: ComponentStorage<TestComponent>,
-        I: Iterator<Item = usize>,
-    >(
-        storage: &mut S,
-        index_iterator: I,
-    ) {
-        for i in index_iterator {
-            let value = i as i32;
-            match storage.insert(i, TestComponent(value)) {
-                Ok(x) => {
-                    if let Some(x) = x {
-                        panic!("inserting into empty slot (index: {}) returned Some ({:?}) as the slot's previous value", i, x)
-                    }
-                }
-                Err(err) => panic!(
-                    "inserting into empty slot (index: {}) returned an error: {:?}",
-                    i, err
-                ),
-            }
-            match storage.get(i) {
-                Some(x) => assert_eq!(
-                    x.0,
-                    value,
-                    "value retrieved from index {}: {:?} did not match what was inserted there: {:?}",
-                    i,
-                    x,
-                    TestComponent(value)
-                ),
-                None => panic!(
-                    "attempt to retrieve the value that was inserted at index {} returned None",
-                    i
-                ),
-            }
-            let updated_value = ((i + 1) * 64) as i32;
-            match storage.insert(i, TestComponent(updated_value)) {
-                Ok(x) => match x {
-                    Some(x) => assert_eq!(x.0, value, "updating value at index {} returned {:?} as the slot's previous value, expected {:?}", i, x, TestComponent(value)),
-                    None => panic!("updating into slot (index: {}) returned None as the slot's previous value, expected {:?}", i, TestComponent(updated_value))
-                },
-                Err(err) => panic!("updating slot (index: {}) returned an error: {:?}", i, err)
-            }
-            match storage.get(i) {
-                Some(x) => assert_eq!(
-                    x.0,
-                    updated_value,
-                    "value retrieved from index {}: {:?} did not match what was updated there: {:?}",
-                    i,
-                    x,
-                    TestComponent(updated_value)
-                ),
-                None => panic!(
-                    "attempt to retrieve the value that was updated at index {} returned None",
-                    i
-                ),
+    /// store every component from an iterator of `(index, component)` pairs, stopping at the
+    /// first one that fails (components stored before the failure remain stored)
+    fn extend(
+        &mut self,
+        components: &mut dyn Iterator<Item = (usize, T)>,
+    ) -> Result<(), ComponentWriteError> {
+        for (index, component) in components {
+            self.insert(index, component)?;
+        }
+        Ok(())
+    }
+
+    /// delete the components at every index (entity ID) from an iterator, returning how many
+    /// components were deleted
+    fn delete_many(
+        &mut self,
+        indices: &mut dyn Iterator<Item = usize>,
+    ) -> Result<usize, ComponentWriteError> {
+        let mut deleted = 0;
+        for index in indices {
+            if self.delete(index)?.is_some() {
+                deleted += 1;
             }
         }
+        Ok(deleted)
     }
+
+    /// keep only the components for which the predicate returns `true`, deleting the rest
+    fn retain(&mut self, predicate: &mut dyn FnMut(usize, &mut T) -> bool) {
+        let rejected: Vec<usize> = self
+            .iter_mut()
+            .filter_map(|(index, component)| (!predicate(index, component)).then_some(index))
+            .collect();
+        for index in rejected {
+            // the index was just yielded by `iter_mut`, so deleting it can't fail
+            let _ = self.delete(index);
+        }
+    }
+}
+
+/// a `ComponentStorage` that keeps its components ordered by index (entity ID), so that it can
+/// answer range queries cheaply
+pub trait OrderedComponentStorage<T: Component>: ComponentStorage<T> {
+    /// iterate over the stored components with indices (entity IDs) in a range, in ascending
+    /// order of index
+    fn range(&self, range: Range<usize>) -> ComponentIter<'_, T>;
+
+    /// get the stored component with the lowest index (entity ID)
+    fn first(&self) -> Option<(usize, &T)>;
+
+    /// get the stored component with the highest index (entity ID)
+    fn last(&self) -> Option<(usize, &T)>;
+
+    /// get the first stored component with an index (entity ID) greater than `index`
+    fn next_after(&self, index: usize) -> Option<(usize, &T)>;
+
+    /// get the last stored component with an index (entity ID) less than `index`
+    fn prev_before(&self, index: usize) -> Option<(usize, &T)>;
 }
@@ -0,0 +1,50 @@
+//! helpers for implementing `OrderedComponentStorage` over a slice of optional components,
+//! where each component's index (entity ID) is its position in the slice
+
+use crate::component::ComponentIter;
+use std::ops::Range;
+
+/// iterate over the components with indices in a range, in ascending order of index
+pub(crate) fn range_of_slots<T>(slots: &[Option<T>], range: Range<usize>) -> ComponentIter<'_, T> {
+    let end = range.end.min(slots.len());
+    let start = range.start.min(end);
+    Box::new(
+        slots[start..end]
+            .iter()
+            .enumerate()
+            .filter_map(move |(offset, component)| Some((start + offset, component.as_ref()?))),
+    )
+}
+
+/// get the component with the lowest index
+pub(crate) fn first_of_slots<T>(slots: &[Option<T>]) -> Option<(usize, &T)> {
+    slots
+        .iter()
+        .enumerate()
+        .find_map(|(index, component)| Some((index, component.as_ref()?)))
+}
+
+/// get the component with the highest index
+pub(crate) fn last_of_slots<T>(slots: &[Option<T>]) -> Option<(usize, &T)> {
+    prev_of_slots_before(slots, slots.len())
+}
+
+/// get the first component with an index greater than `index`
+pub(crate) fn next_of_slots_after<T>(slots: &[Option<T>], index: usize) -> Option<(usize, &T)> {
+    let start = index.checked_add(1)?;
+    slots
+        .get(start..)?
+        .iter()
+        .enumerate()
+        .find_map(|(offset, component)| Some((start + offset, component.as_ref()?)))
+}
+
+/// get the last component with an index less than `index`
+pub(crate) fn prev_of_slots_before<T>(slots: &[Option<T>], index: usize) -> Option<(usize, &T)> {
+    let end = index.min(slots.len());
+    slots[..end]
+        .iter()
+        .enumerate()
+        .rev()
+        .find_map(|(index, component)| Some((index, component.as_ref()?)))
+}
@@ -0,0 +1,409 @@
+use crate::component::disjoint::clear_and_check_disjoint;
+use crate::component::entry::{Entry, InsertVacant, OccupiedEntry, VacantEntry, VacantSlot};
+use crate::component::error::{ComponentWriteError, GetManyMutError};
+use crate::component::memory_usage::{vec_heap_bytes, MemoryUsage};
+use crate::component::{Component, ComponentIter, ComponentIterMut, ComponentStorage};
+
+/// a fixed-size block of component slots, allocated as a whole
+#[derive(Debug)]
+struct Page<T: Component> {
+    components: Box<[Option<T>]>,
+    len: usize,
+}
+
+impl<T> Page<T>
+where
+    T: Component,
+{
+    fn new(size: usize) -> Self {
+        Self {
+            components: (0..size).map(|_| None).collect(),
+            len: 0,
+        }
+    }
+}
+
+/// an implementation of `ComponentStorage` that splits its backing memory into fixed-size
+/// pages of `PAGE_SIZE` slots, only allocating a page once an index inside it is written to
+#[derive(Debug)]
+pub struct PagedVecComponentStorage<T: Component, const PAGE_SIZE: usize = 1024> {
+    pages: Vec<Option<Page<T>>>,
+    len: usize,
+    free_empty_pages: bool,
+}
+
+impl<T, const PAGE_SIZE: usize> PagedVecComponentStorage<T, PAGE_SIZE>
+where
+    T: Component,
+{
+    /// create a new, empty `PagedVecComponentStorage` that keeps its pages once they're empty
+    pub fn new() -> Self {
+        Self::new_with_page_freeing(false)
+    }
+
+    /// create a new `PagedVecComponentStorage`, choosing whether a page should be freed as
+    /// soon as every slot in it is empty (otherwise pages are kept until the storage is dropped)
+    pub fn new_with_page_freeing(free_empty_pages: bool) -> Self {
+        const { assert!(PAGE_SIZE > 0, "PAGE_SIZE must be greater than zero") };
+        Self {
+            pages: Vec::new(),
+            len: 0,
+            free_empty_pages,
+        }
+    }
+
+    /// get the number of pages that currently have backing memory allocated
+    pub fn allocated_pages(&self) -> usize {
+        self.pages.iter().filter(|page| page.is_some()).count()
+    }
+
+    /// get the page (if allocated) and the slot within it for an index (entity ID)
+    fn page(&self, index: usize) -> Option<(&Page<T>, usize)> {
+        let page = self.pages.get(index / PAGE_SIZE)?.as_ref()?;
+        Some((page, index % PAGE_SIZE))
+    }
+
+    /// get the page (if allocated) and the slot within it for an index (entity ID), mutably
+    fn page_mut(&mut self, index: usize) -> Option<(&mut Page<T>, usize)> {
+        let page = self.pages.get_mut(index / PAGE_SIZE)?.as_mut()?;
+        Some((page, index % PAGE_SIZE))
+    }
+}
+
+impl<T, const PAGE_SIZE: usize> ComponentStorage<T> for PagedVecComponentStorage<T, PAGE_SIZE>
+where
+    T: Component,
+{
+    fn get(&self, index: usize) -> Option<&T> {
+        let (page, slot) = self.page(index)?;
+        page.components[slot].as_ref()
+    }
+
+    fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        let (page, slot) = self.page_mut(index)?;
+        page.components[slot].as_mut()
+    }
+
+    fn get_many_mut_into<'a>(
+        &'a mut self,
+        indices: &[usize],
+        components: &mut [Option<&'a mut T>],
+    ) -> Result<(), GetManyMutError> {
+        clear_and_check_disjoint::<T>(indices, components)?;
+        let mut requests: Vec<(usize, usize)> = indices.iter().copied().zip(0..).collect();
+        requests.sort_unstable();
+        // walk forward through the pages (and the slots of each page) in index order, so every
+        // reference comes from a different step of the same iterator
+        let mut pages = self.pages.iter_mut();
+        let mut next_page = 0;
+        let mut slots = None;
+        let mut next_slot = 0;
+        for (index, position) in requests {
+            let page_index = index / PAGE_SIZE;
+            if page_index >= next_page {
+                slots = pages
+                    .nth(page_index - next_page)
+                    .and_then(Option::as_mut)
+                    .map(|page| page.components.iter_mut());
+                next_page = page_index + 1;
+                next_slot = 0;
+            }
+            if let Some(slots) = &mut slots {
+                let slot = index % PAGE_SIZE;
+                components[position] = slots.nth(slot - next_slot).and_then(Option::as_mut);
+                next_slot = slot + 1;
+            }
+        }
+        Ok(())
+    }
+
+    unsafe fn get_unchecked(&self, index: usize) -> &T {
+        // SAFETY: the caller guarantees a component is stored at the index, so its page is
+        // allocated
+        unsafe {
+            let page = self
+                .pages
+                .get_unchecked(index / PAGE_SIZE)
+                .as_ref()
+                .unwrap_unchecked();
+            page.components
+                .get_unchecked(index % PAGE_SIZE)
+                .as_ref()
+                .unwrap_unchecked()
+        }
+    }
+
+    unsafe fn get_unchecked_mut(&mut self, index: usize) -> &mut T {
+        // SAFETY: the caller guarantees a component is stored at the index, so its page is
+        // allocated
+        unsafe {
+            let page = self
+                .pages
+                .get_unchecked_mut(index / PAGE_SIZE)
+                .as_mut()
+                .unwrap_unchecked();
+            page.components
+                .get_unchecked_mut(index % PAGE_SIZE)
+                .as_mut()
+                .unwrap_unchecked()
+        }
+    }
+
+    fn insert(&mut self, index: usize, component: T) -> Result<Option<T>, ComponentWriteError> {
+        let page_index = index / PAGE_SIZE;
+        if page_index >= self.pages.len() {
+            self.pages.resize_with(page_index + 1, || None);
+        }
+        let page = self.pages[page_index].get_or_insert_with(|| Page::new(PAGE_SIZE));
+        let prev = page.components[index % PAGE_SIZE].replace(component);
+        if prev.is_none() {
+            page.len += 1;
+            self.len += 1;
+        }
+        Ok(prev)
+    }
+
+    fn delete(&mut self, index: usize) -> Result<Option<T>, ComponentWriteError> {
+        let free_empty_pages = self.free_empty_pages;
+        let page_index = index / PAGE_SIZE;
+        let Some(Some(page)) = self.pages.get_mut(page_index) else {
+            return Ok(None); // page was never allocated, so there's nothing to delete
+        };
+        let prev = page.components[index % PAGE_SIZE].take();
+        if prev.is_some() {
+            page.len -= 1;
+            self.len -= 1;
+            if page.len == 0 && free_empty_pages {
+                self.pages[page_index] = None;
+            }
+        }
+        Ok(prev)
+    }
+
+    fn entry(&mut self, index: usize) -> Result<Entry<'_, T>, ComponentWriteError> {
+        let page_index = index / PAGE_SIZE;
+        if !self.pages.get(page_index).is_some_and(Option::is_some) {
+            // the page is only allocated once a component is stored in it
+            return Ok(Entry::Vacant(VacantEntry::new(index, self)));
+        }
+        let len = &mut self.len;
+        let Some(Some(page)) = self.pages.get_mut(page_index) else {
+            unreachable!("page was just checked");
+        };
+        let page_len = &mut page.len;
+        Ok(match &mut page.components[index % PAGE_SIZE] {
+            Some(component) => Entry::Occupied(OccupiedEntry::new(index, component)),
+            slot @ None => Entry::Vacant(VacantEntry::with_slot(
+                index,
+                VacantSlot::Option {
+                    slot,
+                    len,
+                    page_len: Some(page_len),
+                },
+            )),
+        })
+    }
+
+    fn iter(&self) -> ComponentIter<'_, T> {
+        Box::new(
+            self.pages
+                .iter()
+                .enumerate()
+                .filter_map(|(page_index, page)| Some((page_index * PAGE_SIZE, page.as_ref()?)))
+                .flat_map(|(offset, page)| {
+                    page.components
+                        .iter()
+                        .enumerate()
+                        .filter_map(move |(slot, component)| {
+                            Some((offset + slot, component.as_ref()?))
+                        })
+                }),
+        )
+    }
+
+    fn iter_mut(&mut self) -> ComponentIterMut<'_, T> {
+        Box::new(
+            self.pages
+                .iter_mut()
+                .enumerate()
+                .filter_map(|(page_index, page)| Some((page_index * PAGE_SIZE, page.as_mut()?)))
+                .flat_map(|(offset, page)| {
+                    page.components
+                        .iter_mut()
+                        .enumerate()
+                        .filter_map(move |(slot, component)| {
+                            Some((offset + slot, component.as_mut()?))
+                        })
+                }),
+        )
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn clear(&mut self) {
+        self.pages.clear();
+        self.len = 0;
+    }
+
+    fn capacity(&self) -> usize {
+        self.allocated_pages() * PAGE_SIZE
+    }
+
+    fn reserve(&mut self, _additional: usize) {
+        // pages are allocated when an index inside them is first written, so which pages would
+        // need reserving can't be known in advance
+    }
+
+    fn shrink_to_fit(&mut self) {
+        for page in self.pages.iter_mut() {
+            if page.as_ref().is_some_and(|page| page.len == 0) {
+                *page = None;
+            }
+        }
+        let used_pages = self
+            .pages
+            .iter()
+            .rposition(Option::is_some)
+            .map_or(0, |last| last + 1);
+        self.pages.truncate(used_pages);
+        self.pages.shrink_to_fit();
+    }
+
+    fn memory_usage(&self) -> MemoryUsage {
+        let page_bytes = self.allocated_pages() * vec_heap_bytes::<Option<T>>(PAGE_SIZE);
+        MemoryUsage::new::<Self>(
+            vec_heap_bytes::<Option<Page<T>>>(self.pages.capacity()) + page_bytes,
+        )
+    }
+
+    fn retain(&mut self, predicate: &mut dyn FnMut(usize, &mut T) -> bool) {
+        for (page_index, stored_page) in self.pages.iter_mut().enumerate() {
+            let Some(page) = stored_page else {
+                continue;
+            };
+            for (slot, stored) in page.components.iter_mut().enumerate() {
+                if let Some(component) = stored {
+                    if !predicate(page_index * PAGE_SIZE + slot, component) {
+                        *stored = None;
+                        page.len -= 1;
+                        self.len -= 1;
+                    }
+                }
+            }
+            if page.len == 0 && self.free_empty_pages {
+                *stored_page = None;
+            }
+        }
+    }
+}
+
+impl<T, const PAGE_SIZE: usize> InsertVacant<T> for PagedVecComponentStorage<T, PAGE_SIZE>
+where
+    T: Component,
+{
+    fn insert_vacant(&mut self, index: usize, component: T) -> &mut T {
+        // only entries in pages that haven't been allocated are left to the storage
+        let page_index = index / PAGE_SIZE;
+        if page_index >= self.pages.len() {
+            self.pages.resize_with(page_index + 1, || None);
+        }
+        let page = self.pages[page_index].get_or_insert_with(|| Page::new(PAGE_SIZE));
+        page.len += 1;
+        self.len += 1;
+        page.components[index % PAGE_SIZE].insert(component)
+    }
+}
+
+impl<T, const PAGE_SIZE: usize> Default for PagedVecComponentStorage<T, PAGE_SIZE>
+where
+    T: Component,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::component::paged_vec_component_storage::PagedVecComponentStorage;
+    use crate::component::testing::{test_conformance, TestComponent};
+    use crate::component::ComponentStorage;
+
+    #[test]
+    fn test_conformance_suite() {
+        test_conformance(PagedVecComponentStorage::<TestComponent, 16>::default, None);
+    }
+
+    #[test]
+    fn test_shrink_frees_empty_pages() {
+        let mut storage: PagedVecComponentStorage<TestComponent, 16> =
+            PagedVecComponentStorage::default();
+        assert!(storage.insert(3, TestComponent(3)).is_ok());
+        assert!(storage.insert(100, TestComponent(100)).is_ok());
+        assert!(storage.delete(100).is_ok());
+        assert_eq!(storage.capacity(), 32);
+        storage.shrink_to_fit();
+        assert_eq!(storage.allocated_pages(), 1, "empty page was not freed");
+        assert_eq!(storage.capacity(), 16);
+        assert_eq!(storage.get(3), Some(&TestComponent(3)));
+    }
+
+    #[test]
+    fn test_only_allocates_written_pages() {
+        let mut storage: PagedVecComponentStorage<TestComponent> =
+            PagedVecComponentStorage::default();
+        assert!(storage.insert(1_000_000, TestComponent(1)).is_ok());
+        assert_eq!(
+            storage.allocated_pages(),
+            1,
+            "inserting a single component allocated more than one page"
+        );
+        assert_eq!(storage.get(1_000_000), Some(&TestComponent(1)));
+        assert!(storage.get(999_999).is_none());
+        assert!(storage.get(0).is_none());
+    }
+
+    #[test]
+    fn test_entry_only_grows_on_insert() {
+        let mut storage: PagedVecComponentStorage<TestComponent, 16> =
+            PagedVecComponentStorage::default();
+        assert!(storage.entry(10_000).is_ok());
+        assert_eq!(
+            storage.memory_usage().heap_bytes,
+            0,
+            "an entry that was never inserted into grew the storage"
+        );
+        match storage.entry(10_000) {
+            Ok(entry) => assert_eq!(entry.or_insert(TestComponent(1)), &TestComponent(1)),
+            Err(err) => panic!("getting an entry returned error {:?}", err),
+        }
+        assert_eq!(storage.get(10_000), Some(&TestComponent(1)));
+    }
+
+    #[test]
+    fn test_frees_empty_pages() {
+        let mut storage: PagedVecComponentStorage<TestComponent, 16> =
+            PagedVecComponentStorage::new_with_page_freeing(true);
+        assert!(storage.insert(3, TestComponent(3)).is_ok());
+        assert!(storage.insert(5, TestComponent(5)).is_ok());
+        assert!(storage.delete(3).is_ok());
+        assert_eq!(
+            storage.allocated_pages(),
+            1,
+            "page was freed while still in use"
+        );
+        assert!(storage.delete(5).is_ok());
+        assert_eq!(storage.allocated_pages(), 0, "empty page was not freed");
+    }
+
+    #[test]
+    fn test_keeps_empty_pages_by_default() {
+        let mut storage: PagedVecComponentStorage<TestComponent, 16> =
+            PagedVecComponentStorage::default();
+        assert!(storage.insert(3, TestComponent(3)).is_ok());
+        assert!(storage.delete(3).is_ok());
+        assert_eq!(storage.allocated_pages(), 1, "empty page was freed");
+    }
+}
@@ -0,0 +1,612 @@
+//! a struct-of-arrays `ComponentStorage`, which stores each field of a component in its own
+//! packed column so that systems can process one field of many components at a time (e.g. with
+//! SIMD)
+
+use crate::component::entry::Entry;
+use crate::component::error::{ComponentWriteError, ComponentWriteErrorKind};
+use crate::component::memory_usage::{vec_heap_bytes, MemoryUsage};
+use crate::component::{Component, ComponentIter, ComponentIterMut, ComponentStorage};
+use std::fmt::{Debug, Formatter};
+use std::iter;
+
+#[cfg(feature = "derive")]
+pub use wyrd_derive::SoaComponent;
+
+/// a component that can be split into columns (one per field) and stored in a
+/// `SoaComponentStorage` (usually implemented with `#[derive(SoaComponent)]`)
+pub trait SoaComponent: Component {
+    /// the set of columns that stores every field of the component
+    type Columns: SoaColumns<Self>;
+}
+
+/// a set of equally long columns, one per field of a component, where the values in the same
+/// position (slot) of every column make up one component
+///
+/// `#[derive(SoaComponent)]` generates an implementation of this that keeps each field in a
+/// `Vec`, along with `Slices` and `SlicesMut` structs with one slice per field and `Ref` and
+/// `RefMut` structs with one reference per field
+pub trait SoaColumns<T>: Default {
+    /// a view of every column as an immutable slice
+    type Slices<'a>
+    where
+        Self: 'a;
+
+    /// a view of every column as a mutable slice (which can't change the length of the columns)
+    type SlicesMut<'a>
+    where
+        Self: 'a;
+
+    /// a view of the fields in one slot, as a reference to each
+    type Ref<'a>
+    where
+        Self: 'a;
+
+    /// a view of the fields in one slot, as a mutable reference to each
+    type RefMut<'a>
+    where
+        Self: 'a;
+
+    /// get the number of slots in each column
+    fn len(&self) -> usize;
+
+    /// check whether the columns are empty
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// split a component into its fields and append them to the end of the columns
+    fn push(&mut self, component: T);
+
+    /// remove the component in a slot, moving the component in the last slot into its place
+    fn swap_remove(&mut self, slot: usize) -> T;
+
+    /// replace the component in a slot, returning the previous one
+    fn replace(&mut self, slot: usize, component: T) -> T;
+
+    /// assemble a copy of the component in a slot from its fields
+    fn read(&self, slot: usize) -> T;
+
+    /// overwrite the fields in a slot with copies of a component's fields
+    fn write(&mut self, slot: usize, component: &T);
+
+    /// remove every slot
+    fn clear(&mut self);
+
+    /// get the number of slots that can be stored without allocating more memory
+    fn capacity(&self) -> usize;
+
+    /// reserve memory for at least `additional` more slots
+    fn reserve(&mut self, additional: usize);
+
+    /// release as much unused memory as possible
+    fn shrink_to_fit(&mut self);
+
+    /// get the number of bytes the columns have allocated on the heap
+    fn heap_bytes(&self) -> usize;
+
+    /// get every column as an immutable slice
+    fn slices(&self) -> Self::Slices<'_>;
+
+    /// get every column as a mutable slice
+    fn slices_mut(&mut self) -> Self::SlicesMut<'_>;
+
+    /// get a reference to each field in a slot
+    fn row(&self, slot: usize) -> Self::Ref<'_>;
+
+    /// get a mutable reference to each field in a slot
+    fn row_mut(&mut self, slot: usize) -> Self::RefMut<'_>;
+}
+
+/// an implementation of `ComponentStorage` that keeps each field of its components packed
+/// together in its own column, with a sparse vector mapping each index (entity ID) to its slot
+/// in the columns
+///
+/// since the columns don't hold whole components, this storage can't hand out references to
+/// them: `get`, `get_mut` and `get_many_mut` never find a component, `iter` and `iter_mut`
+/// yield nothing, and `entry` fails with `ComponentWriteErrorKind::Rejected`. instead, use
+/// `fields` and `fields_mut` to access the fields of one component, `columns` and
+/// `columns_mut` to access a field of every component at once, or `read` to assemble a copy
+pub struct SoaComponentStorage<T: SoaComponent> {
+    // invariant: `columns` and `indices` always have the same length
+    columns: T::Columns,
+    indices: Vec<usize>,
+    sparse: Vec<Option<usize>>,
+}
+
+impl<T> SoaComponentStorage<T>
+where
+    T: SoaComponent,
+{
+    /// create a new, empty `SoaComponentStorage`
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// create a new `SoaComponentStorage` with room for a certain number of components
+    /// reserved in advance (it can still resize beyond this initial size)
+    pub fn new_with_initial_size(initial_size: usize) -> Self {
+        let mut storage = Self::new();
+        storage.reserve(initial_size);
+        storage
+    }
+
+    /// get every column as an immutable slice, in the same order as `indices`
+    pub fn columns(&self) -> <T::Columns as SoaColumns<T>>::Slices<'_> {
+        self.columns.slices()
+    }
+
+    /// get every column as a mutable slice, in the same order as `indices`
+    pub fn columns_mut(&mut self) -> <T::Columns as SoaColumns<T>>::SlicesMut<'_> {
+        self.columns.slices_mut()
+    }
+
+    /// get the index (entity ID) of the component in each slot of the columns
+    pub fn indices(&self) -> &[usize] {
+        &self.indices
+    }
+
+    /// get a reference to each field of the component for an index (entity ID)
+    pub fn fields(&self, index: usize) -> Option<<T::Columns as SoaColumns<T>>::Ref<'_>> {
+        let slot = self.dense_slot(index)?;
+        Some(self.columns.row(slot))
+    }
+
+    /// get a mutable reference to each field of the component for an index (entity ID)
+    pub fn fields_mut(
+        &mut self,
+        index: usize,
+    ) -> Option<<T::Columns as SoaColumns<T>>::RefMut<'_>> {
+        let slot = self.dense_slot(index)?;
+        Some(self.columns.row_mut(slot))
+    }
+
+    /// assemble a copy of the component for an index (entity ID) from its fields
+    pub fn read(&self, index: usize) -> Option<T> {
+        let slot = self.dense_slot(index)?;
+        Some(self.columns.read(slot))
+    }
+
+    /// get the slot in the columns that holds the component for an index (entity ID)
+    fn dense_slot(&self, index: usize) -> Option<usize> {
+        self.sparse.get(index).copied().flatten()
+    }
+
+    /// remove the component in a slot, moving the last component into its place
+    fn remove_slot(&mut self, slot: usize) -> T {
+        let index = self.indices.swap_remove(slot);
+        self.sparse[index] = None;
+        if let Some(&moved) = self.indices.get(slot) {
+            self.sparse[moved] = Some(slot);
+        }
+        self.columns.swap_remove(slot)
+    }
+}
+
+impl<T> ComponentStorage<T> for SoaComponentStorage<T>
+where
+    T: SoaComponent,
+{
+    fn get(&self, _index: usize) -> Option<&T> {
+        None // components are split into columns, so there's no whole component to refer to
+    }
+
+    fn get_mut(&mut self, _index: usize) -> Option<&mut T> {
+        None
+    }
+
+    unsafe fn get_unchecked(&self, index: usize) -> &T {
+        panic!(
+            "SoaComponentStorage can't hand out a reference to the component at {}",
+            index
+        );
+    }
+
+    unsafe fn get_unchecked_mut(&mut self, index: usize) -> &mut T {
+        panic!(
+            "SoaComponentStorage can't hand out a reference to the component at {}",
+            index
+        );
+    }
+
+    fn insert(&mut self, index: usize, component: T) -> Result<Option<T>, ComponentWriteError> {
+        if let Some(slot) = self.dense_slot(index) {
+            return Ok(Some(self.columns.replace(slot, component)));
+        }
+        if index >= self.sparse.len() {
+            self.sparse.resize(index + 1, None);
+        }
+        self.sparse[index] = Some(self.indices.len());
+        self.columns.push(component);
+        self.indices.push(index);
+        Ok(None)
+    }
+
+    fn delete(&mut self, index: usize) -> Result<Option<T>, ComponentWriteError> {
+        match self.dense_slot(index) {
+            Some(slot) => Ok(Some(self.remove_slot(slot))),
+            None => Ok(None), // nothing stored at this index, so there's nothing to delete
+        }
+    }
+
+    fn entry(&mut self, index: usize) -> Result<Entry<'_, T>, ComponentWriteError> {
+        // either kind of entry hands out a mutable reference to a whole component
+        Err(ComponentWriteError::new_with_kind::<T>(
+            index,
+            ComponentWriteErrorKind::Rejected,
+        ))
+    }
+
+    fn iter(&self) -> ComponentIter<'_, T> {
+        Box::new(iter::empty())
+    }
+
+    fn iter_mut(&mut self) -> ComponentIterMut<'_, T> {
+        Box::new(iter::empty())
+    }
+
+    fn len(&self) -> usize {
+        self.indices.len()
+    }
+
+    fn contains(&self, index: usize) -> bool {
+        self.dense_slot(index).is_some()
+    }
+
+    fn clear(&mut self) {
+        self.columns.clear();
+        self.indices.clear();
+        self.sparse.clear();
+    }
+
+    fn capacity(&self) -> usize {
+        self.columns.capacity()
+    }
+
+    fn reserve(&mut self, additional: usize) {
+        self.columns.reserve(additional);
+        self.indices.reserve(additional);
+    }
+
+    fn shrink_to_fit(&mut self) {
+        let sparse_length = self.indices.iter().max().map_or(0, |last| last + 1);
+        self.sparse.truncate(sparse_length);
+        self.sparse.shrink_to_fit();
+        self.columns.shrink_to_fit();
+        self.indices.shrink_to_fit();
+    }
+
+    fn memory_usage(&self) -> MemoryUsage {
+        MemoryUsage::new::<Self>(
+            self.columns.heap_bytes()
+                + vec_heap_bytes::<usize>(self.indices.capacity())
+                + vec_heap_bytes::<Option<usize>>(self.sparse.capacity()),
+        )
+    }
+
+    fn retain(&mut self, predicate: &mut dyn FnMut(usize, &mut T) -> bool) {
+        // walk backwards so that swap-removing a component only ever moves one that has
+        // already been visited into its place
+        for slot in (0..self.indices.len()).rev() {
+            let mut component = self.columns.read(slot);
+            if predicate(self.indices[slot], &mut component) {
+                self.columns.write(slot, &component);
+            } else {
+                self.remove_slot(slot);
+            }
+        }
+    }
+}
+
+impl<T> Default for SoaComponentStorage<T>
+where
+    T: SoaComponent,
+{
+    fn default() -> Self {
+        Self {
+            columns: Default::default(),
+            indices: Default::default(),
+            sparse: Default::default(),
+        }
+    }
+}
+
+impl<T> Debug for SoaComponentStorage<T>
+where
+    T: SoaComponent,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_map()
+            .entries(
+                self.indices
+                    .iter()
+                    .enumerate()
+                    .map(|(slot, index)| (index, self.columns.read(slot))),
+            )
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::component::error::ComponentWriteErrorKind;
+    use crate::component::memory_usage::vec_heap_bytes;
+    use crate::component::soa_component_storage::{SoaColumns, SoaComponent, SoaComponentStorage};
+    use crate::component::testing::TestComponent;
+    use crate::component::ComponentStorage;
+    use std::mem;
+
+    /// a hand-written set of columns, for testing the storage without the derive macro
+    #[derive(Default)]
+    pub struct TestColumns(Vec<i32>);
+
+    impl SoaColumns<TestComponent> for TestColumns {
+        type Slices<'a> = &'a [i32];
+        type SlicesMut<'a> = &'a mut [i32];
+        type Ref<'a> = &'a i32;
+        type RefMut<'a> = &'a mut i32;
+
+        fn len(&self) -> usize {
+            self.0.len()
+        }
+
+        fn push(&mut self, component: TestComponent) {
+            self.0.push(component.0);
+        }
+
+        fn swap_remove(&mut self, slot: usize) -> TestComponent {
+            TestComponent(self.0.swap_remove(slot))
+        }
+
+        fn replace(&mut self, slot: usize, component: TestComponent) -> TestComponent {
+            TestComponent(mem::replace(&mut self.0[slot], component.0))
+        }
+
+        fn read(&self, slot: usize) -> TestComponent {
+            TestComponent(self.0[slot])
+        }
+
+        fn write(&mut self, slot: usize, component: &TestComponent) {
+            self.0[slot] = component.0;
+        }
+
+        fn clear(&mut self) {
+            self.0.clear();
+        }
+
+        fn capacity(&self) -> usize {
+            self.0.capacity()
+        }
+
+        fn reserve(&mut self, additional: usize) {
+            self.0.reserve(additional);
+        }
+
+        fn shrink_to_fit(&mut self) {
+            self.0.shrink_to_fit();
+        }
+
+        fn heap_bytes(&self) -> usize {
+            vec_heap_bytes::<i32>(self.0.capacity())
+        }
+
+        fn slices(&self) -> Self::Slices<'_> {
+            &self.0
+        }
+
+        fn slices_mut(&mut self) -> Self::SlicesMut<'_> {
+            &mut self.0
+        }
+
+        fn row(&self, slot: usize) -> Self::Ref<'_> {
+            &self.0[slot]
+        }
+
+        fn row_mut(&mut self, slot: usize) -> Self::RefMut<'_> {
+            &mut self.0[slot]
+        }
+    }
+
+    impl SoaComponent for TestComponent {
+        type Columns = TestColumns;
+    }
+
+    #[test]
+    fn test_insert_and_delete() {
+        let mut storage: SoaComponentStorage<TestComponent> = SoaComponentStorage::default();
+        for i in 0..4 {
+            assert!(matches!(
+                storage.insert(i, TestComponent(i as i32)),
+                Ok(None)
+            ));
+        }
+        assert!(matches!(
+            storage.insert(2, TestComponent(20)),
+            Ok(Some(TestComponent(2)))
+        ));
+        assert_eq!(storage.len(), 4);
+        assert!(matches!(storage.delete(0), Ok(Some(TestComponent(0)))));
+        assert!(matches!(storage.delete(0), Ok(None)));
+        assert!(!storage.contains(0));
+        assert_eq!(storage.indices(), &[3, 1, 2]);
+        assert_eq!(storage.columns(), &[3, 1, 20]);
+        assert_eq!(storage.read(2), Some(TestComponent(20)));
+        assert_eq!(storage.read(0), None);
+        storage.clear();
+        assert_eq!(storage.len(), 0);
+        assert!(storage.columns().is_empty());
+    }
+
+    #[test]
+    fn test_fields() {
+        let mut storage: SoaComponentStorage<TestComponent> = SoaComponentStorage::default();
+        for i in 0..4 {
+            assert!(storage.insert(i, TestComponent(i as i32)).is_ok());
+        }
+        if let Some(x) = storage.fields_mut(1) {
+            *x = 10;
+        }
+        for x in storage.columns_mut() {
+            *x += 1;
+        }
+        assert_eq!(storage.fields(1), Some(&11));
+        assert_eq!(storage.fields(7), None);
+        assert_eq!(storage.columns(), &[1, 11, 3, 4]);
+    }
+
+    #[test]
+    fn test_retain() {
+        let mut storage: SoaComponentStorage<TestComponent> = SoaComponentStorage::default();
+        for i in 0..8 {
+            assert!(storage.insert(i, TestComponent(i as i32)).is_ok());
+        }
+        storage.retain(&mut |index, component| {
+            component.0 *= 10;
+            index % 2 == 0
+        });
+        assert_eq!(storage.len(), 4);
+        for i in (0..8).step_by(2) {
+            assert_eq!(storage.read(i), Some(TestComponent(i as i32 * 10)));
+        }
+    }
+
+    #[test]
+    fn test_references_are_unsupported() {
+        let mut storage: SoaComponentStorage<TestComponent> = SoaComponentStorage::default();
+        assert!(storage.insert(1, TestComponent(1)).is_ok());
+        assert!(storage.contains(1));
+        assert_eq!(storage.get(1), None);
+        assert_eq!(storage.get_mut(1), None);
+        assert_eq!(storage.iter().count(), 0);
+        match storage.entry(1) {
+            Ok(_) => panic!("getting an entry should have been rejected"),
+            Err(err) => assert_eq!(err.kind(), ComponentWriteErrorKind::Rejected),
+        }
+        assert_eq!(format!("{:?}", storage), "{1: TestComponent(1)}");
+    }
+
+    fn assert_sync<T: Sync>() {}
+
+    #[test]
+    fn test_storage_is_sync() {
+        assert_sync::<SoaComponentStorage<TestComponent>>();
+    }
+
+    #[cfg(feature = "derive")]
+    mod derive {
+        use crate::component::soa_component_storage::{SoaComponent, SoaComponentStorage};
+        use crate::component::{Component, ComponentStorage};
+        use std::fmt::Debug;
+
+        #[derive(Clone, Copy, Debug, PartialEq, SoaComponent)]
+        struct Velocity {
+            x: f32,
+            y: f32,
+            z: f32,
+        }
+
+        impl Component for Velocity {}
+
+        const REVERSE: Velocity = Velocity {
+            x: -1.0,
+            y: -1.0,
+            z: -1.0,
+        };
+
+        #[derive(Debug, PartialEq, SoaComponent)]
+        struct Name(String, u8);
+
+        impl Component for Name {}
+
+        #[derive(Debug, PartialEq, SoaComponent)]
+        struct Pair<T: Debug, const N: usize>
+        where
+            T: Clone,
+        {
+            pair: [T; N],
+            label: &'static str,
+        }
+
+        impl<T: Clone + Debug, const N: usize> Component for Pair<T, N> {}
+
+        #[test]
+        fn test_field_columns() {
+            let mut storage: SoaComponentStorage<Velocity> = SoaComponentStorage::default();
+            for i in 0..4 {
+                let v = i as f32;
+                let velocity = Velocity {
+                    x: v,
+                    y: v * 10.0,
+                    z: v * 100.0,
+                };
+                assert!(storage.insert(i, velocity).is_ok());
+            }
+            let columns = storage.columns();
+            assert_eq!(columns.x, &[0.0, 1.0, 2.0, 3.0]);
+            assert_eq!(columns.y, &[0.0, 10.0, 20.0, 30.0]);
+            assert_eq!(columns.z, &[0.0, 100.0, 200.0, 300.0]);
+
+            // a loop over whole columns, as a system would use for SIMD
+            let columns = storage.columns_mut();
+            for (x, y) in columns.x.iter_mut().zip(columns.y.iter()) {
+                *x += *y;
+            }
+            assert_eq!(
+                storage.read(2),
+                Some(Velocity {
+                    x: 22.0,
+                    y: 20.0,
+                    z: 200.0
+                })
+            );
+            if let Some(fields) = storage.fields_mut(2) {
+                *fields.z = -200.0;
+            }
+            assert_eq!(storage.fields(2).map(|fields| *fields.z), Some(-200.0));
+
+            match storage.insert(2, REVERSE) {
+                Ok(x) => assert_eq!(x.map(|v| v.x), Some(22.0), "insert returned {:?}", x),
+                Err(err) => panic!("updating returned error {:?}", err),
+            }
+            assert_eq!(storage.columns().x, &[0.0, 11.0, -1.0, 33.0]);
+        }
+
+        #[test]
+        fn test_tuple_struct_columns() {
+            let mut storage: SoaComponentStorage<Name> = SoaComponentStorage::default();
+            assert!(storage.insert(5, Name(String::from("orc"), 1)).is_ok());
+            assert!(storage.insert(9, Name(String::from("elf"), 2)).is_ok());
+            if let Some(name) = storage.fields_mut(5) {
+                name.0.push_str("ish");
+            }
+            let columns = storage.columns();
+            assert_eq!(columns.0, &[String::from("orcish"), String::from("elf")]);
+            assert_eq!(columns.1, &[1, 2]);
+            match storage.delete(5) {
+                Ok(x) => assert_eq!(x, Some(Name(String::from("orcish"), 1))),
+                Err(err) => panic!("deleting returned error {:?}", err),
+            }
+            assert_eq!(storage.indices(), &[9]);
+        }
+
+        #[test]
+        fn test_generic_struct_columns() {
+            let mut storage: SoaComponentStorage<Pair<u8, 2>> = SoaComponentStorage::default();
+            let pair = Pair {
+                pair: [1, 2],
+                label: "first",
+            };
+            assert!(storage.insert(0, pair).is_ok());
+            assert_eq!(storage.columns().pair, &[[1, 2]]);
+            assert_eq!(storage.columns().label, &["first"]);
+        }
+
+        fn assert_soa_component<T: SoaComponent>() {}
+
+        #[test]
+        fn test_derives_soa_component() {
+            assert_soa_component::<Velocity>();
+            assert_soa_component::<Name>();
+            assert_soa_component::<Pair<String, 3>>();
+        }
+    }
+}
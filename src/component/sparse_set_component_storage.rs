@@ -0,0 +1,411 @@
+use crate::component::disjoint::get_many_slots_mut;
+use crate::component::entry::{Entry, InsertVacant, OccupiedEntry, VacantEntry};
+use crate::component::error::{ComponentWriteError, GetManyMutError};
+use crate::component::memory_usage::{vec_heap_bytes, MemoryUsage};
+use crate::component::{Component, ComponentIter, ComponentIterMut, ComponentStorage};
+use std::cmp::Ordering;
+use std::mem;
+
+/// an implementation of `ComponentStorage` that keeps components packed together in a dense
+/// vector, with a sparse vector mapping each index (entity ID) to its slot in the dense vector
+#[derive(Debug)]
+pub struct SparseSetComponentStorage<T: Component> {
+    dense: Vec<T>,
+    indices: Vec<usize>,
+    sparse: Vec<Option<usize>>,
+}
+
+impl<T> SparseSetComponentStorage<T>
+where
+    T: Component,
+{
+    /// create a new, empty `SparseSetComponentStorage`
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// create a new `SparseSetComponentStorage` with room for a certain number of components
+    /// reserved in advance (it can still resize beyond this initial size)
+    pub fn new_with_initial_size(initial_size: usize) -> Self {
+        Self {
+            dense: Vec::with_capacity(initial_size),
+            indices: Vec::with_capacity(initial_size),
+            sparse: Vec::with_capacity(initial_size),
+        }
+    }
+
+    /// get the stored components as a packed slice, in iteration order
+    pub fn as_slice(&self) -> &[T] {
+        &self.dense
+    }
+
+    /// get the stored components as a packed mutable slice, in iteration order
+    pub fn as_mut_slice(&mut self) -> &mut [T] {
+        &mut self.dense
+    }
+
+    /// get the index (entity ID) of each component in the packed slice, in iteration order
+    pub fn indices(&self) -> &[usize] {
+        &self.indices
+    }
+
+    /// reorder the packed slice with a comparator (keeping components that compare equal in
+    /// their current order), so that iteration follows the new order
+    ///
+    /// the order lasts until the next sort as long as components are only read, updated or
+    /// replaced: inserting a component for a new index appends it to the end, and deleting one
+    /// moves the last component into its place
+    pub fn sort_by(&mut self, mut compare: impl FnMut(&T, &T) -> Ordering) {
+        // sort a list of slots rather than the components themselves, so that nothing has
+        // moved yet if the comparator panics
+        let dense = &self.dense;
+        let mut order: Vec<usize> = (0..dense.len()).collect();
+        order.sort_by(|a, b| compare(&dense[*a], &dense[*b]));
+        // move the component from slot `order[slot]` into each slot, one cycle at a time
+        for start in 0..order.len() {
+            let mut slot = start;
+            loop {
+                let from = mem::replace(&mut order[slot], slot);
+                if from == start || from == slot {
+                    break;
+                }
+                self.dense.swap(slot, from);
+                self.indices.swap(slot, from);
+                slot = from;
+            }
+        }
+        for (slot, index) in self.indices.iter().enumerate() {
+            self.sparse[*index] = Some(slot);
+        }
+    }
+
+    /// reorder the packed slice by a key (such as a material or a Morton code), keeping
+    /// components with equal keys in their current order (see `sort_by`)
+    pub fn sort_by_key<K: Ord>(&mut self, mut key: impl FnMut(&T) -> K) {
+        self.sort_by(|a, b| key(a).cmp(&key(b)));
+    }
+
+    /// append a component for an index (entity ID) that doesn't have one yet to the end of
+    /// the packed slice
+    fn push(&mut self, index: usize, component: T) -> &mut T {
+        if index >= self.sparse.len() {
+            self.sparse.resize(index + 1, None);
+        }
+        let slot = self.dense.len();
+        self.sparse[index] = Some(slot);
+        self.dense.push(component);
+        self.indices.push(index);
+        &mut self.dense[slot]
+    }
+
+    /// get the slot in the packed slice that holds the component for an index (entity ID)
+    fn dense_slot(&self, index: usize) -> Option<usize> {
+        self.sparse.get(index).copied().flatten()
+    }
+}
+
+impl<T> ComponentStorage<T> for SparseSetComponentStorage<T>
+where
+    T: Component,
+{
+    fn get(&self, index: usize) -> Option<&T> {
+        let slot = self.dense_slot(index)?;
+        self.dense.get(slot)
+    }
+
+    fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        let slot = self.dense_slot(index)?;
+        self.dense.get_mut(slot)
+    }
+
+    fn get_many_mut_into<'a>(
+        &'a mut self,
+        indices: &[usize],
+        components: &mut [Option<&'a mut T>],
+    ) -> Result<(), GetManyMutError> {
+        let sparse = &self.sparse;
+        // SAFETY: the sparse vector never maps two indices to the same dense slot
+        unsafe {
+            get_many_slots_mut(
+                &mut self.dense,
+                indices,
+                components,
+                |index| sparse.get(index).copied().flatten(),
+                Some,
+            )
+        }
+    }
+
+    unsafe fn get_unchecked(&self, index: usize) -> &T {
+        // SAFETY: the caller guarantees a component is stored at the index, so it has a slot
+        unsafe {
+            let slot = self.sparse.get_unchecked(index).unwrap_unchecked();
+            self.dense.get_unchecked(slot)
+        }
+    }
+
+    unsafe fn get_unchecked_mut(&mut self, index: usize) -> &mut T {
+        // SAFETY: the caller guarantees a component is stored at the index, so it has a slot
+        unsafe {
+            let slot = self.sparse.get_unchecked(index).unwrap_unchecked();
+            self.dense.get_unchecked_mut(slot)
+        }
+    }
+
+    fn insert(&mut self, index: usize, component: T) -> Result<Option<T>, ComponentWriteError> {
+        if let Some(slot) = self.dense_slot(index) {
+            return Ok(Some(mem::replace(&mut self.dense[slot], component)));
+        }
+        self.push(index, component);
+        Ok(None)
+    }
+
+    fn delete(&mut self, index: usize) -> Result<Option<T>, ComponentWriteError> {
+        let slot = match self.sparse.get_mut(index) {
+            Some(slot) => slot.take(),
+            None => None,
+        };
+        match slot {
+            Some(slot) => {
+                let component = self.dense.swap_remove(slot);
+                self.indices.swap_remove(slot);
+                if let Some(&moved) = self.indices.get(slot) {
+                    self.sparse[moved] = Some(slot);
+                }
+                Ok(Some(component))
+            }
+            None => Ok(None), // nothing stored at this index, so there's nothing to delete
+        }
+    }
+
+    fn entry(&mut self, index: usize) -> Result<Entry<'_, T>, ComponentWriteError> {
+        Ok(match self.dense_slot(index) {
+            Some(slot) => Entry::Occupied(OccupiedEntry::new(index, &mut self.dense[slot])),
+            None => Entry::Vacant(VacantEntry::new(index, self)),
+        })
+    }
+
+    fn iter(&self) -> ComponentIter<'_, T> {
+        Box::new(self.indices.iter().copied().zip(self.dense.iter()))
+    }
+
+    fn iter_mut(&mut self) -> ComponentIterMut<'_, T> {
+        Box::new(self.indices.iter().copied().zip(self.dense.iter_mut()))
+    }
+
+    fn len(&self) -> usize {
+        self.dense.len()
+    }
+
+    fn contains(&self, index: usize) -> bool {
+        self.dense_slot(index).is_some()
+    }
+
+    fn clear(&mut self) {
+        self.dense.clear();
+        self.indices.clear();
+        self.sparse.clear();
+    }
+
+    fn capacity(&self) -> usize {
+        self.dense.capacity()
+    }
+
+    fn reserve(&mut self, additional: usize) {
+        self.dense.reserve(additional);
+        self.indices.reserve(additional);
+    }
+
+    fn shrink_to_fit(&mut self) {
+        let sparse_length = self.indices.iter().max().map_or(0, |last| last + 1);
+        self.sparse.truncate(sparse_length);
+        self.sparse.shrink_to_fit();
+        self.dense.shrink_to_fit();
+        self.indices.shrink_to_fit();
+    }
+
+    fn memory_usage(&self) -> MemoryUsage {
+        MemoryUsage::new::<Self>(
+            vec_heap_bytes::<T>(self.dense.capacity())
+                + vec_heap_bytes::<usize>(self.indices.capacity())
+                + vec_heap_bytes::<Option<usize>>(self.sparse.capacity()),
+        )
+    }
+
+    fn extend(
+        &mut self,
+        components: &mut dyn Iterator<Item = (usize, T)>,
+    ) -> Result<(), ComponentWriteError> {
+        let (additional, _) = components.size_hint();
+        self.reserve(additional);
+        for (index, component) in components {
+            self.insert(index, component)?;
+        }
+        Ok(())
+    }
+
+    fn retain(&mut self, predicate: &mut dyn FnMut(usize, &mut T) -> bool) {
+        // walk backwards so that swap-removing a component only ever moves one that has
+        // already been visited into its place
+        for slot in (0..self.dense.len()).rev() {
+            let index = self.indices[slot];
+            if !predicate(index, &mut self.dense[slot]) {
+                self.sparse[index] = None;
+                self.dense.swap_remove(slot);
+                self.indices.swap_remove(slot);
+                if let Some(&moved) = self.indices.get(slot) {
+                    self.sparse[moved] = Some(slot);
+                }
+            }
+        }
+    }
+}
+
+impl<T> InsertVacant<T> for SparseSetComponentStorage<T>
+where
+    T: Component,
+{
+    fn insert_vacant(&mut self, index: usize, component: T) -> &mut T {
+        self.push(index, component)
+    }
+}
+
+impl<T> Default for SparseSetComponentStorage<T>
+where
+    T: Component,
+{
+    fn default() -> Self {
+        Self {
+            dense: Default::default(),
+            indices: Default::default(),
+            sparse: Default::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::component::sparse_set_component_storage::SparseSetComponentStorage;
+    use crate::component::testing::{test_conformance, TestComponent};
+    use crate::component::ComponentStorage;
+    use std::panic::{self, AssertUnwindSafe};
+
+    #[test]
+    fn test_conformance_suite() {
+        test_conformance(SparseSetComponentStorage::default, None);
+    }
+
+    #[test]
+    fn test_delete_keeps_components_packed() {
+        let mut storage: SparseSetComponentStorage<TestComponent> =
+            SparseSetComponentStorage::default();
+        for i in [10, 20, 30, 40] {
+            assert!(storage.insert(i, TestComponent(i as i32)).is_ok());
+        }
+        match storage.delete(20) {
+            Ok(x) => assert_eq!(x, Some(TestComponent(20)), "delete returned {:?}", x),
+            Err(err) => panic!("deleting returned error {:?}", err),
+        }
+        assert_eq!(
+            storage.as_slice(),
+            &[TestComponent(10), TestComponent(40), TestComponent(30)],
+            "packed components were not swap-removed"
+        );
+        assert_eq!(storage.indices(), &[10, 40, 30]);
+        assert_eq!(storage.get(40), Some(&TestComponent(40)));
+        assert!(storage.get(20).is_none());
+        match storage.delete(20) {
+            Ok(x) => assert!(x.is_none(), "deleting twice returned {:?}", x),
+            Err(err) => panic!("deleting twice returned error {:?}", err),
+        }
+    }
+
+    #[test]
+    fn test_delete_out_of_bounds() {
+        let mut storage: SparseSetComponentStorage<TestComponent> =
+            SparseSetComponentStorage::default();
+        match storage.delete(123456) {
+            Ok(x) => assert!(x.is_none(), "deleting returned {:?}, None expected", x),
+            Err(err) => panic!("deleting returned error {:?}", err),
+        }
+    }
+
+    #[test]
+    fn test_sort() {
+        let mut storage: SparseSetComponentStorage<TestComponent> =
+            SparseSetComponentStorage::default();
+        for (index, value) in [(4, 3), (9, 1), (2, 5), (7, 1), (0, 4)] {
+            let _ = storage.insert(index, TestComponent(value));
+        }
+        storage.sort_by_key(|component| component.0);
+        // components with equal keys keep their previous order
+        assert_eq!(storage.indices(), &[9, 7, 4, 0, 2]);
+        assert_eq!(
+            storage.as_slice(),
+            &[1, 1, 3, 4, 5].map(TestComponent),
+            "packed slice was not sorted"
+        );
+        for index in [0, 2, 4, 7, 9] {
+            assert!(storage.get(index).is_some(), "index {} was lost", index);
+        }
+        assert_eq!(storage.get(2), Some(&TestComponent(5)));
+        assert_eq!(storage.get(7), Some(&TestComponent(1)));
+
+        // the order survives updates, and is followed by iteration
+        let _ = storage.insert(4, TestComponent(3));
+        if let Some(component) = storage.get_mut(0) {
+            component.0 = 4;
+        }
+        let order: Vec<usize> = storage.iter().map(|(index, _)| index).collect();
+        assert_eq!(order, vec![9, 7, 4, 0, 2]);
+
+        storage.sort_by(|a, b| b.cmp(a));
+        assert_eq!(storage.indices(), &[2, 0, 4, 9, 7]);
+        assert!(matches!(storage.delete(4), Ok(Some(TestComponent(3)))));
+        assert_eq!(storage.get(7), Some(&TestComponent(1)));
+        assert_eq!(storage.get(9), Some(&TestComponent(1)));
+        assert_eq!(storage.len(), 4);
+
+        // longer cycles are moved into place too
+        storage.clear();
+        for index in 0..100 {
+            let _ = storage.insert(index, TestComponent((index as i32 * 37) % 100));
+        }
+        storage.sort_by_key(|component| component.0);
+        assert_eq!(
+            storage.as_slice(),
+            (0..100).map(TestComponent).collect::<Vec<_>>()
+        );
+        for index in 0..100 {
+            assert_eq!(
+                storage.get(index),
+                Some(&TestComponent((index as i32 * 37) % 100)),
+                "index {} was lost",
+                index
+            );
+        }
+    }
+
+    #[test]
+    fn test_sort_with_panicking_comparator() {
+        let mut storage: SparseSetComponentStorage<TestComponent> =
+            SparseSetComponentStorage::default();
+        for (index, value) in [(4, 3), (9, 1), (2, 5)] {
+            let _ = storage.insert(index, TestComponent(value));
+        }
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            storage.sort_by(|_, _| panic!("comparator panicked"));
+        }));
+        assert!(result.is_err());
+        assert_eq!(storage.indices(), &[4, 9, 2]);
+        assert_eq!(storage.len(), 3);
+        for (index, value) in [(4, 3), (9, 1), (2, 5)] {
+            assert_eq!(
+                storage.get(index),
+                Some(&TestComponent(value)),
+                "index {} was lost when the comparator panicked",
+                index
+            );
+        }
+    }
+}
@@ -0,0 +1,417 @@
+use crate::component::bitset::{BitIter, Bitset};
+use crate::component::disjoint::clear_and_check_disjoint;
+use crate::component::entry::{Entry, InsertVacant, OccupiedEntry, VacantEntry};
+use crate::component::error::{ComponentWriteError, GetManyMutError};
+use crate::component::memory_usage::MemoryUsage;
+use crate::component::{Component, ComponentIter, ComponentIterMut, ComponentStorage};
+use std::fmt::{Debug, Formatter};
+use std::iter::Copied;
+use std::marker::PhantomData;
+use std::mem::{self, size_of};
+use std::ptr::NonNull;
+use std::slice::Iter;
+
+/// an implementation of `ComponentStorage` for zero-sized "tag" components (such as markers
+/// like `Enemy` or `Selected`), which stores one bit per index (entity ID) and nothing else
+pub struct TagComponentStorage<T: Component> {
+    // invariant: at least one `T` has been stored (and forgotten) for every index in `tags`,
+    // so `T` is known to be inhabited whenever one is conjured for a tagged index
+    tags: Bitset,
+    len: usize,
+    tag: PhantomData<T>,
+}
+
+impl<T> TagComponentStorage<T>
+where
+    T: Component,
+{
+    /// create a new, empty `TagComponentStorage` (for zero-sized component types only)
+    pub fn new() -> Self {
+        const {
+            assert!(
+                size_of::<T>() == 0,
+                "TagComponentStorage requires a zero-sized type"
+            )
+        };
+        Self {
+            tags: Bitset::new(),
+            len: 0,
+            tag: PhantomData,
+        }
+    }
+
+    /// tag an index (entity ID), returning whether it was newly tagged
+    pub fn set(&mut self, index: usize) -> bool
+    where
+        T: Default,
+    {
+        if self.tags.insert(index) {
+            mem::forget(T::default());
+            self.len += 1;
+            return true;
+        }
+        false
+    }
+
+    /// remove the tag from an index (entity ID), returning whether it was tagged
+    pub fn unset(&mut self, index: usize) -> bool {
+        // dropping the conjured tag gives `T` the chance to run its `Drop` implementation
+        matches!(self.delete(index), Ok(Some(_)))
+    }
+
+    /// check whether an index (entity ID) is tagged
+    pub fn is_set(&self, index: usize) -> bool {
+        self.tags.contains(index)
+    }
+
+    /// iterate over the tagged indices (entity IDs), in ascending order
+    pub fn indices(&self) -> BitIter<Copied<Iter<'_, u64>>> {
+        self.tags.iter()
+    }
+
+    /// get the set of tagged indices, which can be combined with the occupancy of other
+    /// storages to find entities that have several components
+    pub fn tags(&self) -> &Bitset {
+        &self.tags
+    }
+
+    /// get a reference to a tag
+    ///
+    /// # Safety
+    /// `T` must be inhabited (i.e. a `T` must have been stored at some point)
+    unsafe fn tag_ref<'a>() -> &'a T {
+        // SAFETY: `T` is zero-sized, so any well-aligned non-null pointer is valid for it
+        unsafe { NonNull::dangling().as_ref() }
+    }
+
+    /// get a mutable reference to a tag (since `T` is zero-sized, these never alias)
+    ///
+    /// # Safety
+    /// `T` must be inhabited (i.e. a `T` must have been stored at some point)
+    unsafe fn tag_mut<'a>() -> &'a mut T {
+        // SAFETY: `T` is zero-sized, so any well-aligned non-null pointer is valid for it
+        unsafe { NonNull::dangling().as_mut() }
+    }
+
+    /// take ownership of a tag that was previously stored (and forgotten)
+    ///
+    /// # Safety
+    /// `T` must be inhabited (i.e. a `T` must have been stored at some point)
+    unsafe fn tag_read() -> T {
+        // SAFETY: `T` is zero-sized, so any well-aligned non-null pointer is valid for it
+        unsafe { NonNull::dangling().read() }
+    }
+}
+
+impl<T> ComponentStorage<T> for TagComponentStorage<T>
+where
+    T: Component,
+{
+    fn get(&self, index: usize) -> Option<&T> {
+        // SAFETY: a tag was stored for every tagged index
+        self.tags
+            .contains(index)
+            .then(|| unsafe { Self::tag_ref() })
+    }
+
+    fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        // SAFETY: a tag was stored for every tagged index
+        self.tags
+            .contains(index)
+            .then(|| unsafe { Self::tag_mut() })
+    }
+
+    fn get_many_mut_into<'a>(
+        &'a mut self,
+        indices: &[usize],
+        components: &mut [Option<&'a mut T>],
+    ) -> Result<(), GetManyMutError> {
+        clear_and_check_disjoint::<T>(indices, components)?;
+        for (index, component) in indices.iter().zip(components.iter_mut()) {
+            // SAFETY: a tag was stored for every tagged index
+            *component = self
+                .tags
+                .contains(*index)
+                .then(|| unsafe { Self::tag_mut() });
+        }
+        Ok(())
+    }
+
+    unsafe fn get_unchecked(&self, _index: usize) -> &T {
+        // SAFETY: the caller guarantees the index is tagged, so a tag was stored
+        unsafe { Self::tag_ref() }
+    }
+
+    unsafe fn get_unchecked_mut(&mut self, _index: usize) -> &mut T {
+        // SAFETY: the caller guarantees the index is tagged, so a tag was stored
+        unsafe { Self::tag_mut() }
+    }
+
+    fn insert(&mut self, index: usize, component: T) -> Result<Option<T>, ComponentWriteError> {
+        // the tag is "stored" by forgetting it, and conjured back when it's deleted
+        mem::forget(component);
+        if self.tags.insert(index) {
+            self.len += 1;
+            return Ok(None);
+        }
+        // SAFETY: a tag was just stored
+        Ok(Some(unsafe { Self::tag_read() }))
+    }
+
+    fn delete(&mut self, index: usize) -> Result<Option<T>, ComponentWriteError> {
+        if self.tags.remove(index) {
+            self.len -= 1;
+            // SAFETY: a tag was stored for the index
+            return Ok(Some(unsafe { Self::tag_read() }));
+        }
+        Ok(None)
+    }
+
+    fn entry(&mut self, index: usize) -> Result<Entry<'_, T>, ComponentWriteError> {
+        if self.tags.contains(index) {
+            // SAFETY: a tag was stored for the index
+            return Ok(Entry::Occupied(OccupiedEntry::new(index, unsafe {
+                Self::tag_mut()
+            })));
+        }
+        Ok(Entry::Vacant(VacantEntry::new(index, self)))
+    }
+
+    fn iter(&self) -> ComponentIter<'_, T> {
+        // SAFETY: a tag was stored for every tagged index
+        Box::new(
+            self.tags
+                .iter()
+                .map(|index| (index, unsafe { Self::tag_ref() })),
+        )
+    }
+
+    fn iter_mut(&mut self) -> ComponentIterMut<'_, T> {
+        // SAFETY: a tag was stored for every tagged index
+        Box::new(
+            self.tags
+                .iter()
+                .map(|index| (index, unsafe { Self::tag_mut() })),
+        )
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn contains(&self, index: usize) -> bool {
+        self.tags.contains(index)
+    }
+
+    fn clear(&mut self) {
+        let cleared = mem::replace(&mut self.len, 0);
+        self.tags.clear();
+        if mem::needs_drop::<T>() {
+            for _ in 0..cleared {
+                // SAFETY: a tag was stored for every index that was tagged before clearing
+                drop(unsafe { Self::tag_read() });
+            }
+        }
+    }
+
+    fn capacity(&self) -> usize {
+        self.tags.capacity()
+    }
+
+    fn reserve(&mut self, additional: usize) {
+        self.tags.reserve(additional);
+    }
+
+    fn shrink_to_fit(&mut self) {
+        self.tags.shrink_to_fit();
+    }
+
+    fn memory_usage(&self) -> MemoryUsage {
+        MemoryUsage::new::<Self>(self.tags.heap_bytes())
+    }
+
+    fn retain(&mut self, predicate: &mut dyn FnMut(usize, &mut T) -> bool) {
+        let rejected: Vec<usize> = self
+            .indices()
+            // SAFETY: a tag was stored for every tagged index
+            .filter(|index| !predicate(*index, unsafe { Self::tag_mut() }))
+            .collect();
+        for index in rejected {
+            self.unset(index);
+        }
+    }
+}
+
+impl<T> InsertVacant<T> for TagComponentStorage<T>
+where
+    T: Component,
+{
+    fn insert_vacant(&mut self, index: usize, component: T) -> &mut T {
+        mem::forget(component);
+        self.tags.insert(index);
+        self.len += 1;
+        // SAFETY: a tag was just stored
+        unsafe { Self::tag_mut() }
+    }
+}
+
+impl<T> Default for TagComponentStorage<T>
+where
+    T: Component,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Debug for TagComponentStorage<T>
+where
+    T: Component,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_set().entries(self.indices()).finish()
+    }
+}
+
+impl<T> Drop for TagComponentStorage<T>
+where
+    T: Component,
+{
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::component::tag_component_storage::TagComponentStorage;
+    use crate::component::{Component, ComponentStorage};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[derive(Debug, Default, Eq, PartialEq)]
+    struct Selected;
+
+    impl Component for Selected {}
+
+    static DROPPED_TAGS: AtomicUsize = AtomicUsize::new(0);
+
+    #[derive(Debug)]
+    struct DropCounter;
+
+    impl Component for DropCounter {}
+
+    impl Drop for DropCounter {
+        fn drop(&mut self) {
+            DROPPED_TAGS.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn test_init() {
+        let storage: TagComponentStorage<Selected> = TagComponentStorage::default();
+        for i in 0..64 {
+            assert!(storage.get(i).is_none(), "element {} was not None", i);
+        }
+        assert!(storage.is_empty());
+    }
+
+    #[test]
+    fn test_insert_and_delete() {
+        let mut storage: TagComponentStorage<Selected> = TagComponentStorage::default();
+        match storage.insert(100, Selected) {
+            Ok(x) => assert!(x.is_none(), "inserting returned {:?}, None expected", x),
+            Err(err) => panic!("inserting returned error {:?}", err),
+        }
+        match storage.insert(100, Selected) {
+            Ok(x) => assert_eq!(x, Some(Selected), "updating returned {:?}", x),
+            Err(err) => panic!("updating returned error {:?}", err),
+        }
+        assert_eq!(storage.get(100), Some(&Selected));
+        assert!(storage.get_mut(100).is_some());
+        assert_eq!(storage.len(), 1);
+        match storage.delete(100) {
+            Ok(x) => assert_eq!(x, Some(Selected), "deleting returned {:?}", x),
+            Err(err) => panic!("deleting returned error {:?}", err),
+        }
+        assert!(storage.get(100).is_none());
+        assert!(storage.is_empty());
+    }
+
+    #[test]
+    fn test_set_unset() {
+        let mut storage: TagComponentStorage<Selected> = TagComponentStorage::default();
+        assert!(storage.set(7), "setting a new tag returned false");
+        assert!(!storage.set(7), "setting an existing tag returned true");
+        assert!(storage.is_set(7));
+        assert!(!storage.is_set(8));
+        assert!(storage.unset(7), "unsetting an existing tag returned false");
+        assert!(!storage.unset(7), "unsetting a missing tag returned true");
+        assert!(!storage.is_set(7));
+    }
+
+    #[test]
+    fn test_iter() {
+        let mut storage: TagComponentStorage<Selected> = TagComponentStorage::default();
+        for i in [3, 64, 65, 1000] {
+            storage.set(i);
+        }
+        assert_eq!(storage.indices().collect::<Vec<_>>(), [3, 64, 65, 1000]);
+        assert_eq!(
+            storage.iter().map(|(index, _)| index).collect::<Vec<_>>(),
+            [3, 64, 65, 1000]
+        );
+        storage.retain(&mut |index, _| index > 64);
+        assert_eq!(storage.indices().collect::<Vec<_>>(), [65, 1000]);
+        assert_eq!(storage.len(), 2);
+    }
+
+    #[test]
+    fn test_entry() {
+        let mut storage: TagComponentStorage<Selected> = TagComponentStorage::default();
+        match storage.entry(5) {
+            Ok(entry) => {
+                entry.or_default();
+            }
+            Err(err) => panic!("getting an entry returned error {:?}", err),
+        }
+        assert!(storage.is_set(5));
+        assert_eq!(storage.len(), 1);
+    }
+
+    #[test]
+    fn test_get_many_mut() {
+        let mut storage: TagComponentStorage<Selected> = TagComponentStorage::default();
+        storage.set(2);
+        storage.set(70);
+        match storage.get_many_mut([70, 3, 2]) {
+            Ok(x) => assert!(
+                matches!(x, [Some(_), None, Some(_)]),
+                "get_many_mut returned {:?}",
+                x
+            ),
+            Err(err) => panic!("get_many_mut returned error {:?}", err),
+        }
+        assert!(storage.get_many_mut([2, 2]).is_err());
+        // SAFETY: index 2 is tagged
+        assert_eq!(unsafe { storage.get_unchecked(2) }, &Selected);
+        // SAFETY: as above
+        assert_eq!(unsafe { storage.get_unchecked_mut(2) }, &mut Selected);
+    }
+
+    #[test]
+    fn test_drops_stored_tags() {
+        let mut storage: TagComponentStorage<DropCounter> = TagComponentStorage::default();
+        for i in 0..4 {
+            assert!(storage.insert(i, DropCounter).is_ok());
+        }
+        assert_eq!(DROPPED_TAGS.load(Ordering::SeqCst), 0);
+        assert!(storage.delete(0).is_ok());
+        assert_eq!(DROPPED_TAGS.load(Ordering::SeqCst), 1);
+        drop(storage);
+        assert_eq!(
+            DROPPED_TAGS.load(Ordering::SeqCst),
+            4,
+            "tags were not dropped with the storage"
+        );
+    }
+}
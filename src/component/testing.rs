@@ -0,0 +1,841 @@
+//! a conformance test suite that any `ComponentStorage` implementation can run to check that it
+//! behaves the same way as the built-in storages (enabled by the `testing` feature)
+
+use crate::component::error::ComponentWriteErrorKind;
+use crate::component::{Component, ComponentIter, ComponentStorage, OrderedComponentStorage};
+use std::collections::BTreeMap;
+use std::mem;
+use std::ops::Range;
+
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Ord, PartialOrd)]
+/// a fake component type for use in unit tests
+pub struct TestComponent(pub i32);
+
+impl Component for TestComponent {}
+
+/// test that a `ComponentStorage` satisfies the expected core initialization behavior
+pub fn test_init_behavior<S: ComponentStorage<TestComponent>, I: Iterator<Item = usize>>(
+    storage: &S,
+    index_iterator: I,
+) {
+    for i in index_iterator {
+        assert!(
+            storage.get(i).is_none(),
+            "element {} in newly initialized storage was not None",
+            i
+        )
+    }
+}
+
+/// test that a `ComponentStorage` satisfies the expected core insertion and updating behavior
+pub fn test_insert_and_update_behavior<
+    S: ComponentStorage<TestComponent>,
+    I: Iterator<Item = usize>,
+>(
+    storage: &mut S,
+    index_iterator: I,
+) {
+    for i in index_iterator {
+        let value = i as i32;
+        match storage.insert(i, TestComponent(value)) {
+            Ok(x) => {
+                if let Some(x) = x {
+                    panic!("inserting into empty slot (index: {}) returned Some ({:?}) as the slot's previous value", i, x)
+                }
+            }
+            Err(err) => panic!(
+                "inserting into empty slot (index: {}) returned an error: {:?}",
+                i, err
+            ),
+        }
+        match storage.get(i) {
+            Some(x) => assert_eq!(
+                x.0,
+                value,
+                "value retrieved from index {}: {:?} did not match what was inserted there: {:?}",
+                i,
+                x,
+                TestComponent(value)
+            ),
+            None => panic!(
+                "attempt to retrieve the value that was inserted at index {} returned None",
+                i
+            ),
+        }
+        let updated_value = ((i + 1) * 64) as i32;
+        match storage.insert(i, TestComponent(updated_value)) {
+            Ok(x) => match x {
+                Some(x) => assert_eq!(x.0, value, "updating value at index {} returned {:?} as the slot's previous value, expected {:?}", i, x, TestComponent(value)),
+                None => panic!("updating into slot (index: {}) returned None as the slot's previous value, expected {:?}", i, TestComponent(updated_value))
+            },
+            Err(err) => panic!("updating slot (index: {}) returned an error: {:?}", i, err)
+        }
+        match storage.get(i) {
+            Some(x) => assert_eq!(
+                x.0,
+                updated_value,
+                "value retrieved from index {}: {:?} did not match what was updated there: {:?}",
+                i,
+                x,
+                TestComponent(updated_value)
+            ),
+            None => panic!(
+                "attempt to retrieve the value that was updated at index {} returned None",
+                i
+            ),
+        }
+    }
+}
+
+/// test that a `ComponentStorage` yields exactly the stored components from `iter` and
+/// `iter_mut`, and that changes made through `iter_mut` are persisted
+pub fn test_iter_behavior<S: ComponentStorage<TestComponent>, I: Iterator<Item = usize>>(
+    storage: &mut S,
+    index_iterator: I,
+) {
+    let mut expected = BTreeMap::new();
+    for i in index_iterator {
+        if let Err(err) = storage.insert(i, TestComponent(i as i32)) {
+            panic!(
+                "inserting into slot (index: {}) returned an error: {:?}",
+                i, err
+            );
+        }
+        expected.insert(i, TestComponent(i as i32));
+    }
+
+    let mut found: BTreeMap<usize, TestComponent> = BTreeMap::new();
+    for (i, component) in storage.iter() {
+        if found.insert(i, *component).is_some() {
+            panic!("iter yielded index {} more than once", i);
+        }
+    }
+    assert_eq!(
+        found, expected,
+        "iter did not yield exactly the stored components"
+    );
+
+    for (i, component) in storage.iter_mut() {
+        component.0 = -(i as i32);
+    }
+    for i in expected.keys() {
+        match storage.get(*i) {
+            Some(x) => assert_eq!(
+                x.0,
+                -(*i as i32),
+                "value at index {} was {:?} after being modified through iter_mut",
+                i,
+                x
+            ),
+            None => panic!(
+                "attempt to retrieve the value modified through iter_mut at index {} returned None",
+                i
+            ),
+        }
+    }
+}
+
+/// test that a `ComponentStorage` allows stored components to be modified in place
+/// through `get_mut`, and returns `None` for empty slots
+pub fn test_get_mut_behavior<S: ComponentStorage<TestComponent>, I: Iterator<Item = usize>>(
+    storage: &mut S,
+    index_iterator: I,
+) {
+    for i in index_iterator {
+        if let Some(x) = storage.get_mut(i) {
+            panic!(
+                "get_mut on empty slot (index: {}) returned Some ({:?})",
+                i, x
+            );
+        }
+        if let Err(err) = storage.insert(i, TestComponent(i as i32)) {
+            panic!(
+                "inserting into slot (index: {}) returned an error: {:?}",
+                i, err
+            );
+        }
+        match storage.get_mut(i) {
+            Some(x) => x.0 += 1,
+            None => panic!("get_mut on occupied slot (index: {}) returned None", i),
+        }
+        match storage.get(i) {
+            Some(x) => assert_eq!(
+                x.0,
+                i as i32 + 1,
+                "value retrieved from index {}: {:?} did not reflect the change made through get_mut",
+                i,
+                x
+            ),
+            None => panic!(
+                "attempt to retrieve the value modified through get_mut at index {} returned None",
+                i
+            ),
+        }
+    }
+}
+
+/// test that a `ComponentStorage` keeps an accurate count of its components, reports
+/// which indices it contains, and removes everything when cleared
+pub fn test_len_and_clear_behavior<
+    S: ComponentStorage<TestComponent>,
+    I: Iterator<Item = usize>,
+>(
+    storage: &mut S,
+    index_iterator: I,
+) {
+    assert!(
+        storage.is_empty(),
+        "newly initialized storage was not empty"
+    );
+    let indices: Vec<usize> = index_iterator.collect();
+    for (count, i) in indices.iter().enumerate() {
+        assert!(!storage.contains(*i), "storage contained empty index {}", i);
+        if let Err(err) = storage.insert(*i, TestComponent(*i as i32)) {
+            panic!(
+                "inserting into slot (index: {}) returned an error: {:?}",
+                i, err
+            );
+        }
+        if let Err(err) = storage.insert(*i, TestComponent(*i as i32)) {
+            panic!("updating slot (index: {}) returned an error: {:?}", i, err);
+        }
+        assert!(storage.contains(*i), "storage did not contain index {}", i);
+        assert_eq!(storage.len(), count + 1, "len was wrong after inserting");
+    }
+    if let Some(i) = indices.first() {
+        if let Err(err) = storage.delete(*i) {
+            panic!("deleting slot (index: {}) returned an error: {:?}", i, err);
+        }
+        assert!(
+            !storage.contains(*i),
+            "storage contained deleted index {}",
+            i
+        );
+        assert_eq!(
+            storage.len(),
+            indices.len() - 1,
+            "len was wrong after deleting"
+        );
+    }
+    assert!(
+        storage.capacity() >= storage.len(),
+        "capacity was smaller than len"
+    );
+    storage.clear();
+    assert!(storage.is_empty(), "storage was not empty after clearing");
+    assert_eq!(
+        storage.iter().count(),
+        0,
+        "iter yielded components after clearing"
+    );
+    for i in indices {
+        assert!(
+            storage.get(i).is_none(),
+            "index {} was not empty after clearing",
+            i
+        );
+    }
+    storage.shrink_to_fit();
+    assert!(storage.is_empty(), "storage was not empty after shrinking");
+}
+
+/// test that a `ComponentStorage` stores, deletes and filters components in bulk
+pub fn test_bulk_behavior<S: ComponentStorage<TestComponent>>(storage: &mut S) {
+    if let Err(err) = storage.extend(&mut (0..64).map(|i| (i, TestComponent(i as i32)))) {
+        panic!("extending returned an error: {:?}", err);
+    }
+    assert_eq!(storage.len(), 64, "extending did not store every component");
+    for i in 0..64 {
+        assert_eq!(
+            storage.get(i),
+            Some(&TestComponent(i as i32)),
+            "value at index {} was wrong after extending",
+            i
+        );
+    }
+
+    match storage.delete_many(&mut (0..16)) {
+        Ok(deleted) => assert_eq!(deleted, 16, "delete_many deleted {} components", deleted),
+        Err(err) => panic!("delete_many returned an error: {:?}", err),
+    }
+    match storage.delete_many(&mut (8..24).step_by(2)) {
+        Ok(deleted) => assert_eq!(deleted, 4, "delete_many deleted {} components", deleted),
+        Err(err) => panic!("delete_many returned an error: {:?}", err),
+    }
+    assert_eq!(storage.len(), 44, "len was wrong after delete_many");
+
+    storage.retain(&mut |index, component| {
+        component.0 += 1000;
+        index % 3 == 0
+    });
+    for i in 0..64 {
+        let expected = (i >= 24 || (i >= 16 && i % 2 == 1)) && i % 3 == 0;
+        match storage.get(i) {
+            Some(x) => {
+                assert!(expected, "index {} was not deleted by retain", i);
+                assert_eq!(x.0, i as i32 + 1000, "retain did not persist changes");
+            }
+            None => assert!(!expected, "index {} was deleted by retain", i),
+        }
+    }
+    assert_eq!(
+        storage.len(),
+        storage.iter().count(),
+        "len was wrong after retain"
+    );
+}
+
+/// test that a `ComponentStorage` hands out mutable references to several components at once,
+/// refuses indices that repeat, and reads and writes components through the unchecked accessors
+pub fn test_get_many_mut_behavior<S: ComponentStorage<TestComponent>>(
+    storage: &mut S,
+    max_index: usize,
+) {
+    let (a, b, missing) = (1, max_index / 2, max_index - 1);
+    for i in [a, b] {
+        if let Err(err) = storage.insert(i, TestComponent(i as i32)) {
+            panic!("inserting (index: {}) returned an error: {:?}", i, err);
+        }
+    }
+    match storage.get_many_mut([a, b, missing]) {
+        Ok([Some(x), Some(y), None]) => mem::swap(x, y),
+        Ok(x) => panic!("get_many_mut returned {:?}", x),
+        Err(err) => panic!("get_many_mut returned an error: {:?}", err),
+    }
+    assert_eq!(storage.get(a), Some(&TestComponent(b as i32)));
+    assert_eq!(storage.get(b), Some(&TestComponent(a as i32)));
+
+    match storage.get_many_mut([b, missing, b]) {
+        Ok(x) => panic!("get_many_mut with a repeated index returned {:?}", x),
+        Err(err) => assert_eq!(
+            err.entity_id(),
+            b,
+            "get_many_mut reported the wrong repeated index"
+        ),
+    }
+
+    let dynamic: &mut dyn ComponentStorage<TestComponent> = storage;
+    let mut components = [None, None];
+    match dynamic.get_many_mut_into(&[b, a], &mut components) {
+        Ok(()) => match components {
+            [Some(x), Some(y)] => {
+                x.0 += 10;
+                y.0 += 20;
+            }
+            x => panic!("get_many_mut_into through a trait object returned {:?}", x),
+        },
+        Err(err) => panic!(
+            "get_many_mut_into through a trait object returned an error: {:?}",
+            err
+        ),
+    }
+    assert_eq!(storage.get(a), Some(&TestComponent(b as i32 + 20)));
+    assert_eq!(storage.get(b), Some(&TestComponent(a as i32 + 10)));
+
+    storage.clear();
+    let indices: Vec<usize> = (0..max_index).step_by(2).rev().collect();
+    for i in (0..max_index).step_by(4) {
+        if let Err(err) = storage.insert(i, TestComponent(i as i32)) {
+            panic!("inserting (index: {}) returned an error: {:?}", i, err);
+        }
+    }
+    let mut components: Vec<Option<&mut TestComponent>> = indices.iter().map(|_| None).collect();
+    if let Err(err) = storage.get_many_mut_into(&indices, &mut components) {
+        panic!("get_many_mut_into returned an error: {:?}", err);
+    }
+    for (i, component) in indices.iter().zip(components) {
+        match component {
+            Some(x) => {
+                assert_eq!(x.0, *i as i32, "get_many_mut_into returned the wrong value");
+                x.0 = -x.0;
+            }
+            None => assert!(i % 4 != 0, "get_many_mut_into missed index {}", i),
+        }
+    }
+    for i in (0..max_index).step_by(4) {
+        // SAFETY: a component was just stored at the index
+        let x = unsafe { storage.get_unchecked_mut(i) };
+        assert_eq!(
+            x.0,
+            -(i as i32),
+            "change made through get_many_mut_into was lost"
+        );
+        x.0 = i as i32 * 2;
+        // SAFETY: as above
+        let x = unsafe { storage.get_unchecked(i) };
+        assert_eq!(
+            x.0,
+            i as i32 * 2,
+            "change made through get_unchecked_mut was lost"
+        );
+    }
+}
+
+/// test that a `ComponentStorage` supports inserting and modifying components in place
+/// through `entry`
+pub fn test_entry_behavior<S: ComponentStorage<TestComponent>, I: Iterator<Item = usize>>(
+    storage: &mut S,
+    index_iterator: I,
+) {
+    for i in index_iterator {
+        match storage.entry(i) {
+            Ok(entry) => assert_eq!(
+                entry.index(),
+                i,
+                "entry for index {} had the wrong index",
+                i
+            ),
+            Err(err) => panic!("getting entry (index: {}) returned an error: {:?}", i, err),
+        }
+        assert!(
+            storage.get(i).is_none(),
+            "getting an entry without inserting stored a component at index {}",
+            i
+        );
+        let len = storage.len();
+
+        match storage.entry(i) {
+            Ok(entry) => {
+                let component = entry.and_modify(|x| x.0 = -1).or_insert(TestComponent(1));
+                assert_eq!(
+                    component.0, 1,
+                    "or_insert into empty slot (index: {}) returned {:?}",
+                    i, component
+                );
+                component.0 += 1;
+            }
+            Err(err) => panic!("getting entry (index: {}) returned an error: {:?}", i, err),
+        }
+        assert_eq!(
+            storage.len(),
+            len + 1,
+            "or_insert into empty slot did not update len"
+        );
+        assert_eq!(
+            storage.get(i),
+            Some(&TestComponent(2)),
+            "change made through entry was not persisted"
+        );
+
+        match storage.entry(i) {
+            Ok(entry) => {
+                let component = entry
+                    .and_modify(|x| x.0 *= 10)
+                    .or_insert_with(|| panic!("or_insert_with called default for occupied slot"));
+                assert_eq!(
+                    component.0, 20,
+                    "and_modify on occupied slot (index: {}) produced {:?}",
+                    i, component
+                );
+            }
+            Err(err) => panic!("getting entry (index: {}) returned an error: {:?}", i, err),
+        }
+        assert_eq!(
+            storage.len(),
+            len + 1,
+            "modifying through entry changed len"
+        );
+
+        if let Err(err) = storage.delete(i) {
+            panic!("deleting slot (index: {}) returned an error: {:?}", i, err);
+        }
+        match storage.entry(i) {
+            Ok(entry) => assert_eq!(*entry.or_default(), TestComponent::default()),
+            Err(err) => panic!("getting entry (index: {}) returned an error: {:?}", i, err),
+        }
+        assert_eq!(
+            storage.get(i),
+            Some(&TestComponent::default()),
+            "or_default did not store a component"
+        );
+    }
+}
+
+/// test that deleting from a `ComponentStorage` returns the deleted component, leaves the slot
+/// empty, and returns `None` for slots that are already empty
+pub fn test_delete_behavior<S: ComponentStorage<TestComponent>, I: Iterator<Item = usize>>(
+    storage: &mut S,
+    index_iterator: I,
+) {
+    for i in index_iterator {
+        match storage.delete(i) {
+            Ok(x) => assert!(
+                x.is_none(),
+                "deleting empty slot (index: {}) returned Some ({:?})",
+                i,
+                x
+            ),
+            Err(err) => panic!(
+                "deleting empty slot (index: {}) returned an error: {:?}",
+                i, err
+            ),
+        }
+        if let Err(err) = storage.insert(i, TestComponent(i as i32)) {
+            panic!(
+                "inserting into slot (index: {}) returned an error: {:?}",
+                i, err
+            );
+        }
+        match storage.delete(i) {
+            Ok(x) => assert_eq!(
+                x,
+                Some(TestComponent(i as i32)),
+                "deleting slot (index: {}) returned {:?} as the slot's previous value",
+                i,
+                x
+            ),
+            Err(err) => panic!("deleting slot (index: {}) returned an error: {:?}", i, err),
+        }
+        assert!(
+            storage.get(i).is_none(),
+            "slot (index: {}) was not empty after deleting",
+            i
+        );
+        match storage.delete(i) {
+            Ok(x) => assert!(
+                x.is_none(),
+                "deleting slot (index: {}) twice returned Some ({:?})",
+                i,
+                x
+            ),
+            Err(err) => panic!(
+                "deleting slot (index: {}) twice returned an error: {:?}",
+                i, err
+            ),
+        }
+    }
+}
+
+/// test that a fixed-size `ComponentStorage` rejects writes to indices at or beyond its
+/// capacity, without changing what it stores
+pub fn test_out_of_range_behavior<S: ComponentStorage<TestComponent>, I: Iterator<Item = usize>>(
+    storage: &mut S,
+    index_iterator: I,
+) {
+    let len = storage.len();
+    for i in index_iterator {
+        assert!(
+            storage.get(i).is_none(),
+            "get out of range (index: {}) returned Some",
+            i
+        );
+        assert!(
+            storage.get_mut(i).is_none(),
+            "get_mut out of range (index: {}) returned Some",
+            i
+        );
+        assert!(
+            !storage.contains(i),
+            "storage contained out of range index {}",
+            i
+        );
+        match storage.insert(i, TestComponent(i as i32)) {
+            Ok(x) => panic!(
+                "inserting out of range (index: {}) succeeded, returning {:?}",
+                i, x
+            ),
+            Err(err) => assert!(
+                matches!(err.kind(), ComponentWriteErrorKind::OutOfBounds { .. }),
+                "inserting out of range (index: {}) returned the wrong kind of error: {:?}",
+                i,
+                err
+            ),
+        }
+        if let Ok(entry) = storage.entry(i) {
+            panic!(
+                "getting entry out of range (index: {}) returned {:?}",
+                i, entry
+            );
+        }
+        if let Ok(Some(x)) = storage.delete(i) {
+            panic!(
+                "deleting out of range (index: {}) returned Some ({:?})",
+                i, x
+            );
+        }
+        assert_eq!(
+            storage.len(),
+            len,
+            "len changed after writing out of range (index: {})",
+            i
+        );
+    }
+}
+
+/// test that an `OrderedComponentStorage` answers range and neighbor queries in index order
+/// (the storage must be able to hold indices up to 64)
+pub fn test_ordered_behavior<S: OrderedComponentStorage<TestComponent>>(storage: &mut S) {
+    assert!(
+        storage.first().is_none(),
+        "first of an empty storage was Some"
+    );
+    assert!(
+        storage.last().is_none(),
+        "last of an empty storage was Some"
+    );
+    assert!(
+        storage.next_after(0).is_none(),
+        "next_after in an empty storage was Some"
+    );
+    assert!(
+        storage.prev_before(64).is_none(),
+        "prev_before in an empty storage was Some"
+    );
+    for i in [5, 9, 17, 40, 63] {
+        if let Err(err) = storage.insert(i, TestComponent(i as i32)) {
+            panic!("inserting (index: {}) returned an error: {:?}", i, err);
+        }
+    }
+    let pairs = |iter: ComponentIter<'_, TestComponent>| -> Vec<(usize, i32)> {
+        iter.map(|(index, x)| (index, x.0)).collect()
+    };
+    assert_eq!(
+        pairs(storage.range(9..40)),
+        [(9, 9), (17, 17)],
+        "range did not include the start and exclude the end"
+    );
+    assert_eq!(
+        pairs(storage.range(0..usize::MAX)),
+        [(5, 5), (9, 9), (17, 17), (40, 40), (63, 63)],
+        "range over every index did not yield every component in order"
+    );
+    assert!(
+        storage.range(18..40).next().is_none(),
+        "range without components yielded Some"
+    );
+    assert!(
+        storage.range(Range { start: 40, end: 9 }).next().is_none(),
+        "decreasing range yielded Some"
+    );
+    assert_eq!(storage.first(), Some((5, &TestComponent(5))));
+    assert_eq!(storage.last(), Some((63, &TestComponent(63))));
+    assert_eq!(storage.next_after(5), Some((9, &TestComponent(9))));
+    assert_eq!(storage.next_after(10), Some((17, &TestComponent(17))));
+    assert_eq!(storage.next_after(63), None);
+    assert_eq!(storage.next_after(usize::MAX), None);
+    assert_eq!(storage.prev_before(9), Some((5, &TestComponent(5))));
+    assert_eq!(storage.prev_before(39), Some((17, &TestComponent(17))));
+    assert_eq!(
+        storage.prev_before(usize::MAX),
+        Some((63, &TestComponent(63)))
+    );
+    assert_eq!(storage.prev_before(5), None);
+    assert_eq!(storage.prev_before(0), None);
+}
+
+/// a small, deterministic pseudo-random number generator (xorshift64*) for driving
+/// randomized tests without extra dependencies
+struct TestRng(u64);
+
+impl TestRng {
+    fn new(seed: u64) -> Self {
+        // the state must never be zero
+        Self(seed ^ 0x9E37_79B9_7F4A_7C15 | 1)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    fn below(&mut self, bound: usize) -> usize {
+        (self.next() % bound as u64) as usize
+    }
+}
+
+/// test that a `ComponentStorage` behaves exactly like a `BTreeMap` (as a reference model)
+/// over a randomized sequence of operations on indices below `max_index`
+pub fn test_model_behavior<S: ComponentStorage<TestComponent>>(
+    storage: &mut S,
+    max_index: usize,
+    operations: usize,
+    seed: u64,
+) {
+    let mut model: BTreeMap<usize, TestComponent> = BTreeMap::new();
+    let mut rng = TestRng::new(seed);
+    for step in 0..operations {
+        let i = rng.below(max_index);
+        let value = TestComponent(rng.below(1000) as i32);
+        match rng.below(100) {
+            0..=29 => match storage.insert(i, value) {
+                Ok(x) => assert_eq!(
+                    x,
+                    model.insert(i, value),
+                    "step {}: insert (index: {}) returned the wrong previous value",
+                    step,
+                    i
+                ),
+                Err(err) => panic!(
+                    "step {}: insert (index: {}) returned an error: {:?}",
+                    step, i, err
+                ),
+            },
+            30..=49 => match storage.delete(i) {
+                Ok(x) => assert_eq!(
+                    x,
+                    model.remove(&i),
+                    "step {}: delete (index: {}) returned the wrong previous value",
+                    step,
+                    i
+                ),
+                Err(err) => panic!(
+                    "step {}: delete (index: {}) returned an error: {:?}",
+                    step, i, err
+                ),
+            },
+            50..=59 => {
+                if let Some(x) = storage.get_mut(i) {
+                    x.0 += 1;
+                }
+                if let Some(x) = model.get_mut(&i) {
+                    x.0 += 1;
+                }
+            }
+            60..=69 => match storage.entry(i) {
+                Ok(entry) => {
+                    let x = entry.and_modify(|x| x.0 -= 1).or_insert(value);
+                    let expected = model.entry(i).and_modify(|x| x.0 -= 1).or_insert(value);
+                    assert_eq!(
+                        x, expected,
+                        "step {}: entry (index: {}) returned the wrong value",
+                        step, i
+                    );
+                }
+                Err(err) => panic!(
+                    "step {}: entry (index: {}) returned an error: {:?}",
+                    step, i, err
+                ),
+            },
+            70..=74 => {
+                let start = rng.below(max_index);
+                let end = (start + rng.below(8)).min(max_index);
+                let pairs: Vec<(usize, TestComponent)> =
+                    (start..end).map(|i| (i, TestComponent(i as i32))).collect();
+                if let Err(err) = storage.extend(&mut pairs.clone().into_iter()) {
+                    panic!("step {}: extend returned an error: {:?}", step, err);
+                }
+                model.extend(pairs);
+            }
+            75..=79 => {
+                let start = rng.below(max_index);
+                let end = (start + rng.below(8)).min(max_index);
+                let expected = model.range(start..end).count();
+                for index in start..end {
+                    model.remove(&index);
+                }
+                match storage.delete_many(&mut (start..end)) {
+                    Ok(deleted) => assert_eq!(
+                        deleted, expected,
+                        "step {}: delete_many ({}..{}) deleted the wrong number of components",
+                        step, start, end
+                    ),
+                    Err(err) => panic!("step {}: delete_many returned an error: {:?}", step, err),
+                }
+            }
+            80..=82 => {
+                let modulus = rng.below(4) as i32 + 2;
+                storage.retain(&mut |_, x| x.0 % modulus != 0);
+                model.retain(|_, x| x.0 % modulus != 0);
+            }
+            83 => {
+                storage.clear();
+                model.clear();
+            }
+            84 => storage.shrink_to_fit(),
+            85..=89 => {
+                let others = [rng.below(max_index), rng.below(max_index)];
+                let distinct = i != others[0] && i != others[1] && others[0] != others[1];
+                match storage.get_many_mut([i, others[0], others[1]]) {
+                    Ok(components) => {
+                        assert!(
+                            distinct,
+                            "step {}: get_many_mut ({}, {:?}) accepted a repeated index",
+                            step, i, others
+                        );
+                        for (index, x) in [i, others[0], others[1]].into_iter().zip(components) {
+                            assert_eq!(
+                                x.as_deref(),
+                                model.get(&index),
+                                "step {}: get_many_mut (index: {}) returned the wrong value",
+                                step,
+                                index
+                            );
+                            if let (Some(x), Some(expected)) = (x, model.get_mut(&index)) {
+                                x.0 += 1;
+                                expected.0 += 1;
+                            }
+                        }
+                    }
+                    Err(_) => assert!(
+                        !distinct,
+                        "step {}: get_many_mut ({}, {:?}) refused distinct indices",
+                        step, i, others
+                    ),
+                }
+            }
+            _ => {
+                assert_eq!(
+                    storage.get(i),
+                    model.get(&i),
+                    "step {}: get (index: {}) returned the wrong value",
+                    step,
+                    i
+                );
+                assert_eq!(
+                    storage.contains(i),
+                    model.contains_key(&i),
+                    "step {}: contains (index: {}) was wrong",
+                    step,
+                    i
+                );
+            }
+        }
+        assert_eq!(storage.len(), model.len(), "step {}: len was wrong", step);
+        if step % 64 == 0 {
+            let stored: BTreeMap<usize, TestComponent> =
+                storage.iter().map(|(index, x)| (index, *x)).collect();
+            assert_eq!(stored, model, "step {}: iter did not match the model", step);
+        }
+    }
+    let stored: BTreeMap<usize, TestComponent> =
+        storage.iter().map(|(index, x)| (index, *x)).collect();
+    assert_eq!(
+        stored, model,
+        "iter did not match the model after every operation"
+    );
+}
+
+/// run every test in the conformance suite against fresh storages made by `new_storage`
+///
+/// `capacity` is the number of indices a fixed-size storage can hold (`None` for storages that
+/// can hold any index); it must be at least 64
+pub fn test_conformance<S: ComponentStorage<TestComponent>, F: Fn() -> S>(
+    new_storage: F,
+    capacity: Option<usize>,
+) {
+    assert!(
+        capacity.is_none_or(|capacity| capacity >= 64),
+        "the conformance suite needs storages that can hold at least 64 components"
+    );
+    let max_index = capacity.unwrap_or(1024).min(1024);
+
+    test_init_behavior(&new_storage(), 0..max_index);
+    test_insert_and_update_behavior(&mut new_storage(), 0..max_index);
+    test_get_mut_behavior(&mut new_storage(), 0..max_index);
+    test_delete_behavior(&mut new_storage(), 0..max_index);
+    test_iter_behavior(&mut new_storage(), (0..max_index).step_by(3));
+    test_len_and_clear_behavior(&mut new_storage(), (0..max_index).step_by(3));
+    test_bulk_behavior(&mut new_storage());
+    test_entry_behavior(&mut new_storage(), (0..max_index).step_by(3));
+    test_get_many_mut_behavior(&mut new_storage(), max_index);
+    for seed in 0..4 {
+        test_model_behavior(&mut new_storage(), max_index, 2000, seed);
+    }
+    if let Some(capacity) = capacity {
+        let mut storage = new_storage();
+        test_insert_and_update_behavior(&mut storage, 0..capacity);
+        test_out_of_range_behavior(&mut storage, capacity..capacity + 64);
+    }
+}
@@ -0,0 +1,543 @@
+use crate::component::entry::{Entry, InsertObserver};
+use crate::component::error::{ComponentWriteError, GetManyMutError};
+use crate::component::memory_usage::{b_tree_map_heap_bytes, vec_heap_bytes, MemoryUsage};
+use crate::component::{Component, ComponentIter, ComponentIterMut, ComponentStorage};
+use std::collections::BTreeSet;
+use std::marker::PhantomData;
+use std::mem::{self, size_of};
+
+/// an identifier for a reader of the changes recorded by a `TrackedStorage`
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct ReaderId {
+    slot: usize,
+    // distinguishes readers that have used the same slot, so an ID kept after its reader was
+    // unregistered never refers to a reader registered later
+    generation: u32,
+}
+
+/// the indices (entity IDs) whose component was added, changed or removed since a reader last
+/// drained its changes from a `TrackedStorage`
+///
+/// changes are recorded by their net effect: a component that was added and then changed is
+/// only reported as added, one that was added and then removed isn't reported at all, and one
+/// that was removed and then added again is reported as changed
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ComponentChanges {
+    pub added: BTreeSet<usize>,
+    pub changed: BTreeSet<usize>,
+    pub removed: BTreeSet<usize>,
+}
+
+impl ComponentChanges {
+    /// check whether no changes were recorded
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.changed.is_empty() && self.removed.is_empty()
+    }
+
+    fn record_added(&mut self, index: usize) {
+        if self.removed.remove(&index) {
+            self.changed.insert(index);
+        } else {
+            self.added.insert(index);
+        }
+    }
+
+    fn record_changed(&mut self, index: usize) {
+        if !self.added.contains(&index) {
+            self.changed.insert(index);
+        }
+    }
+
+    fn heap_bytes(&self) -> usize {
+        [&self.added, &self.changed, &self.removed]
+            .into_iter()
+            .map(|indices| b_tree_map_heap_bytes::<usize, ()>(indices.len()))
+            .sum()
+    }
+
+    fn record_removed(&mut self, index: usize) {
+        self.changed.remove(&index);
+        if !self.added.remove(&index) {
+            self.removed.insert(index);
+        }
+    }
+}
+
+/// the pending changes for every registered reader
+#[derive(Debug, Default)]
+struct Readers(Vec<ReaderSlot>);
+
+/// a slot for a reader's pending changes, which is reused once its reader is unregistered
+#[derive(Debug, Default)]
+struct ReaderSlot {
+    generation: u32,
+    changes: Option<ComponentChanges>,
+}
+
+impl Readers {
+    fn heap_bytes(&self) -> usize {
+        let changes: usize = self.iter().map(|changes| changes.heap_bytes()).sum();
+        vec_heap_bytes::<ReaderSlot>(self.0.capacity()) + changes
+    }
+
+    /// get the pending changes of every registered reader
+    fn iter(&self) -> impl Iterator<Item = &ComponentChanges> {
+        self.0.iter().filter_map(|slot| slot.changes.as_ref())
+    }
+
+    /// get the pending changes of every registered reader, mutably
+    fn iter_mut(&mut self) -> impl Iterator<Item = &mut ComponentChanges> {
+        self.0.iter_mut().filter_map(|slot| slot.changes.as_mut())
+    }
+
+    /// get the slot of a reader, if it's still registered
+    fn get_mut(&mut self, reader: ReaderId) -> Option<&mut ReaderSlot> {
+        self.0
+            .get_mut(reader.slot)
+            .filter(|slot| slot.generation == reader.generation && slot.changes.is_some())
+    }
+
+    fn record_added(&mut self, index: usize) {
+        for changes in self.iter_mut() {
+            changes.record_added(index);
+        }
+    }
+
+    fn record_changed(&mut self, index: usize) {
+        for changes in self.iter_mut() {
+            changes.record_changed(index);
+        }
+    }
+
+    fn record_removed(&mut self, index: usize) {
+        for changes in self.iter_mut() {
+            changes.record_removed(index);
+        }
+    }
+}
+
+impl InsertObserver for Readers {
+    fn inserted(&mut self, index: usize) {
+        self.record_added(index);
+    }
+}
+
+/// a wrapper around any `ComponentStorage` that records which indices (entity IDs) had a
+/// component added, changed or removed, so that any number of independent readers can each
+/// drain every change exactly once
+///
+/// any mutable access to a component (through `get_mut`, `iter_mut`, `entry` or `retain`) is
+/// recorded as a change, whether or not the component was actually modified. this makes
+/// `retain` a coarse approximation: every component it keeps is reported as changed, even if the
+/// predicate only looked at it
+#[derive(Debug)]
+pub struct TrackedStorage<T: Component, S: ComponentStorage<T>> {
+    storage: S,
+    readers: Readers,
+    component: PhantomData<T>,
+}
+
+impl<T, S> TrackedStorage<T, S>
+where
+    T: Component,
+    S: ComponentStorage<T>,
+{
+    /// create a new `TrackedStorage` wrapping a storage (components already stored in it are
+    /// not reported as added)
+    pub fn new(storage: S) -> Self {
+        Self {
+            storage,
+            readers: Readers::default(),
+            component: PhantomData,
+        }
+    }
+
+    /// register a new reader, which will see every change made from now on
+    pub fn register_reader(&mut self) -> ReaderId {
+        let slots = &mut self.readers.0;
+        let slot = match slots.iter().position(|slot| slot.changes.is_none()) {
+            Some(free) => free,
+            None => {
+                slots.push(ReaderSlot::default());
+                slots.len() - 1
+            }
+        };
+        slots[slot].changes = Some(ComponentChanges::default());
+        ReaderId {
+            slot,
+            generation: slots[slot].generation,
+        }
+    }
+
+    /// unregister a reader, discarding any changes it hasn't drained yet
+    pub fn unregister_reader(&mut self, reader: ReaderId) {
+        if let Some(slot) = self.readers.get_mut(reader) {
+            slot.changes = None;
+            slot.generation = slot.generation.wrapping_add(1);
+        }
+    }
+
+    /// take every change recorded for a reader since it last drained its changes
+    /// (returns `None` if the reader isn't registered)
+    pub fn drain_changes(&mut self, reader: ReaderId) -> Option<ComponentChanges> {
+        let changes = self.readers.get_mut(reader)?.changes.as_mut()?;
+        Some(mem::take(changes))
+    }
+
+    /// get a reference to the wrapped storage
+    pub fn storage(&self) -> &S {
+        &self.storage
+    }
+
+    /// unwrap the wrapped storage, discarding every recorded change
+    pub fn into_storage(self) -> S {
+        self.storage
+    }
+}
+
+impl<T, S> ComponentStorage<T> for TrackedStorage<T, S>
+where
+    T: Component,
+    S: ComponentStorage<T>,
+{
+    fn get(&self, index: usize) -> Option<&T> {
+        self.storage.get(index)
+    }
+
+    fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        let component = self.storage.get_mut(index)?;
+        self.readers.record_changed(index);
+        Some(component)
+    }
+
+    fn get_many_mut_into<'a>(
+        &'a mut self,
+        indices: &[usize],
+        components: &mut [Option<&'a mut T>],
+    ) -> Result<(), GetManyMutError> {
+        self.storage.get_many_mut_into(indices, components)?;
+        for (index, component) in indices.iter().zip(components.iter()) {
+            if component.is_some() {
+                self.readers.record_changed(*index);
+            }
+        }
+        Ok(())
+    }
+
+    unsafe fn get_unchecked(&self, index: usize) -> &T {
+        // SAFETY: upheld by the caller
+        unsafe { self.storage.get_unchecked(index) }
+    }
+
+    unsafe fn get_unchecked_mut(&mut self, index: usize) -> &mut T {
+        self.readers.record_changed(index);
+        // SAFETY: upheld by the caller
+        unsafe { self.storage.get_unchecked_mut(index) }
+    }
+
+    fn insert(&mut self, index: usize, component: T) -> Result<Option<T>, ComponentWriteError> {
+        let prev = self.storage.insert(index, component)?;
+        match prev {
+            Some(_) => self.readers.record_changed(index),
+            None => self.readers.record_added(index),
+        }
+        Ok(prev)
+    }
+
+    fn delete(&mut self, index: usize) -> Result<Option<T>, ComponentWriteError> {
+        let prev = self.storage.delete(index)?;
+        if prev.is_some() {
+            self.readers.record_removed(index);
+        }
+        Ok(prev)
+    }
+
+    fn entry(&mut self, index: usize) -> Result<Entry<'_, T>, ComponentWriteError> {
+        let readers = &mut self.readers;
+        Ok(match self.storage.entry(index)? {
+            Entry::Occupied(entry) => {
+                readers.record_changed(index);
+                Entry::Occupied(entry)
+            }
+            Entry::Vacant(entry) => Entry::Vacant(entry.observed_by(readers)),
+        })
+    }
+
+    fn iter(&self) -> ComponentIter<'_, T> {
+        self.storage.iter()
+    }
+
+    fn iter_mut(&mut self) -> ComponentIterMut<'_, T> {
+        let readers = &mut self.readers;
+        Box::new(
+            self.storage
+                .iter_mut()
+                .inspect(move |(index, _)| readers.record_changed(*index)),
+        )
+    }
+
+    fn len(&self) -> usize {
+        self.storage.len()
+    }
+
+    fn contains(&self, index: usize) -> bool {
+        self.storage.contains(index)
+    }
+
+    fn clear(&mut self) {
+        for (index, _) in self.storage.iter() {
+            self.readers.record_removed(index);
+        }
+        self.storage.clear();
+    }
+
+    fn capacity(&self) -> usize {
+        self.storage.capacity()
+    }
+
+    fn reserve(&mut self, additional: usize) {
+        self.storage.reserve(additional);
+    }
+
+    fn shrink_to_fit(&mut self) {
+        self.storage.shrink_to_fit();
+    }
+
+    fn memory_usage(&self) -> MemoryUsage {
+        // the wrapped storage is counted inline as part of this one
+        let storage = self.storage.memory_usage();
+        MemoryUsage {
+            inline_bytes: size_of::<Self>(),
+            heap_bytes: storage.heap_bytes + self.readers.heap_bytes(),
+            mapped_bytes: storage.mapped_bytes,
+        }
+    }
+
+    fn delete_many(
+        &mut self,
+        indices: &mut dyn Iterator<Item = usize>,
+    ) -> Result<usize, ComponentWriteError> {
+        // every index is passed through to the wrapped storage so it can report its own errors;
+        // removals are recorded from what it actually deleted
+        let indices: Vec<usize> = indices.collect();
+        let existing: BTreeSet<usize> = indices
+            .iter()
+            .copied()
+            .filter(|index| self.storage.contains(*index))
+            .collect();
+        let result = self.storage.delete_many(&mut indices.into_iter());
+        for index in existing {
+            if !self.storage.contains(index) {
+                self.readers.record_removed(index);
+            }
+        }
+        result
+    }
+
+    fn retain(&mut self, predicate: &mut dyn FnMut(usize, &mut T) -> bool) {
+        let readers = &mut self.readers;
+        self.storage.retain(&mut |index, component| {
+            let keep = predicate(index, component);
+            match keep {
+                true => readers.record_changed(index),
+                false => readers.record_removed(index),
+            }
+            keep
+        });
+    }
+}
+
+impl<T, S> Default for TrackedStorage<T, S>
+where
+    T: Component,
+    S: ComponentStorage<T> + Default,
+{
+    fn default() -> Self {
+        Self::new(S::default())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::component::array_component_storage::ArrayComponentStorage;
+    use crate::component::b_tree_map_component_storage::BTreeMapComponentStorage;
+    use crate::component::error::ComponentWriteErrorKind;
+    use crate::component::testing::{test_conformance, TestComponent};
+    use crate::component::tracked_storage::{ComponentChanges, TrackedStorage};
+    use crate::component::vec_component_storage::VecComponentStorage;
+    use crate::component::ComponentStorage;
+    use std::collections::BTreeSet;
+
+    type TrackedVecStorage = TrackedStorage<TestComponent, VecComponentStorage<TestComponent>>;
+
+    fn changes(added: &[usize], changed: &[usize], removed: &[usize]) -> ComponentChanges {
+        ComponentChanges {
+            added: added.iter().copied().collect::<BTreeSet<_>>(),
+            changed: changed.iter().copied().collect::<BTreeSet<_>>(),
+            removed: removed.iter().copied().collect::<BTreeSet<_>>(),
+        }
+    }
+
+    #[test]
+    fn test_conformance_suite() {
+        test_conformance(TrackedVecStorage::default, None);
+    }
+
+    #[test]
+    fn test_records_changes() {
+        let mut storage = TrackedVecStorage::default();
+        let reader = storage.register_reader();
+        assert!(storage.insert(1, TestComponent(1)).is_ok());
+        assert!(storage.insert(2, TestComponent(2)).is_ok());
+        assert!(storage.insert(3, TestComponent(3)).is_ok());
+        assert_eq!(
+            storage.drain_changes(reader),
+            Some(changes(&[1, 2, 3], &[], &[]))
+        );
+
+        assert!(storage.insert(1, TestComponent(10)).is_ok());
+        if let Some(component) = storage.get_mut(2) {
+            component.0 = 20;
+        }
+        assert!(storage.delete(3).is_ok());
+        assert!(storage.delete(4).is_ok());
+        assert_eq!(
+            storage.drain_changes(reader),
+            Some(changes(&[], &[1, 2], &[3]))
+        );
+        assert_eq!(storage.drain_changes(reader), Some(changes(&[], &[], &[])));
+    }
+
+    #[test]
+    fn test_records_changes_to_many() {
+        let mut storage = TrackedVecStorage::default();
+        assert!(storage.insert(1, TestComponent(1)).is_ok());
+        assert!(storage.insert(2, TestComponent(2)).is_ok());
+        let reader = storage.register_reader();
+        assert!(storage.get_many_mut([1, 2, 3]).is_ok());
+        assert!(storage.get_many_mut([1, 4, 1]).is_err());
+        // SAFETY: a component is stored at index 1
+        unsafe { storage.get_unchecked_mut(1) }.0 = 10;
+        assert_eq!(
+            storage.drain_changes(reader),
+            Some(changes(&[], &[1, 2], &[]))
+        );
+        // SAFETY: as above
+        let _ = unsafe { storage.get_unchecked(1) };
+        assert_eq!(storage.drain_changes(reader), Some(changes(&[], &[], &[])));
+    }
+
+    #[test]
+    fn test_records_net_changes() {
+        let mut storage = TrackedVecStorage::default();
+        assert!(storage.insert(1, TestComponent(1)).is_ok());
+        let reader = storage.register_reader();
+        assert!(storage.insert(2, TestComponent(2)).is_ok());
+        assert!(storage.insert(2, TestComponent(20)).is_ok());
+        assert!(storage.insert(3, TestComponent(3)).is_ok());
+        assert!(storage.delete(3).is_ok());
+        assert!(storage.delete(1).is_ok());
+        assert!(storage.insert(1, TestComponent(10)).is_ok());
+        assert_eq!(
+            storage.drain_changes(reader),
+            Some(changes(&[2], &[1], &[]))
+        );
+    }
+
+    #[test]
+    fn test_independent_readers() {
+        let mut storage: TrackedStorage<TestComponent, BTreeMapComponentStorage<TestComponent>> =
+            TrackedStorage::default();
+        let first = storage.register_reader();
+        assert!(storage.insert(1, TestComponent(1)).is_ok());
+        let second = storage.register_reader();
+        assert!(storage.insert(2, TestComponent(2)).is_ok());
+        assert_eq!(
+            storage.drain_changes(first),
+            Some(changes(&[1, 2], &[], &[]))
+        );
+        storage.retain(&mut |index, _| index != 1);
+        assert_eq!(storage.drain_changes(first), Some(changes(&[], &[2], &[1])));
+        assert_eq!(
+            storage.drain_changes(second),
+            Some(changes(&[2], &[], &[1]))
+        );
+
+        storage.unregister_reader(second);
+        assert_eq!(storage.drain_changes(second), None);
+        let third = storage.register_reader();
+        assert!(storage.delete_many(&mut (0..10)).is_ok());
+        assert_eq!(storage.drain_changes(third), Some(changes(&[], &[], &[2])));
+    }
+
+    #[test]
+    fn test_delete_many_reports_wrapped_storage_errors() {
+        let mut storage: TrackedStorage<TestComponent, ArrayComponentStorage<TestComponent, 4>> =
+            TrackedStorage::default();
+        assert!(storage.insert(1, TestComponent(1)).is_ok());
+        let reader = storage.register_reader();
+        match storage.delete_many(&mut [1, 8].into_iter()) {
+            Ok(x) => panic!("deleting out of bounds returned {:?}", x),
+            Err(err) => assert_eq!(
+                err.kind(),
+                ComponentWriteErrorKind::OutOfBounds { capacity: 4 }
+            ),
+        }
+        assert_eq!(storage.drain_changes(reader), Some(changes(&[], &[], &[1])));
+    }
+
+    #[test]
+    fn test_extend_records_only_stored_components() {
+        let mut storage: TrackedStorage<TestComponent, ArrayComponentStorage<TestComponent, 4>> =
+            TrackedStorage::default();
+        assert!(storage.insert(1, TestComponent(1)).is_ok());
+        assert!(storage.insert(2, TestComponent(2)).is_ok());
+        let reader = storage.register_reader();
+        let components = [(0, 0), (1, 10), (8, 80), (2, 20)];
+        match storage.extend(&mut components.map(|(i, x)| (i, TestComponent(x))).into_iter()) {
+            Ok(()) => panic!("extending out of bounds succeeded"),
+            Err(err) => assert_eq!(
+                err.kind(),
+                ComponentWriteErrorKind::OutOfBounds { capacity: 4 }
+            ),
+        }
+        // index 2 came after the failure, so it was never changed
+        assert_eq!(storage.get(2), Some(&TestComponent(2)));
+        assert_eq!(
+            storage.drain_changes(reader),
+            Some(changes(&[0], &[1], &[]))
+        );
+    }
+
+    #[test]
+    fn test_nested_entries_record_additions() {
+        let mut inner_storage = TrackedVecStorage::default();
+        let inner = inner_storage.register_reader();
+        let mut storage = TrackedStorage::new(inner_storage);
+        let outer = storage.register_reader();
+        match storage.entry(3) {
+            Ok(entry) => assert_eq!(entry.or_insert(TestComponent(3)), &TestComponent(3)),
+            Err(err) => panic!("getting an entry returned error {:?}", err),
+        }
+        assert_eq!(storage.drain_changes(outer), Some(changes(&[3], &[], &[])));
+        let mut inner_storage = storage.into_storage();
+        assert_eq!(
+            inner_storage.drain_changes(inner),
+            Some(changes(&[3], &[], &[]))
+        );
+    }
+
+    #[test]
+    fn test_stale_reader_does_not_alias_new_reader() {
+        let mut storage: TrackedStorage<TestComponent, BTreeMapComponentStorage<TestComponent>> =
+            TrackedStorage::default();
+        let stale = storage.register_reader();
+        storage.unregister_reader(stale);
+        let fresh = storage.register_reader();
+        assert_ne!(stale, fresh);
+        assert!(storage.insert(1, TestComponent(1)).is_ok());
+        assert_eq!(storage.drain_changes(stale), None);
+        storage.unregister_reader(stale);
+        assert_eq!(storage.drain_changes(fresh), Some(changes(&[1], &[], &[])));
+    }
+}
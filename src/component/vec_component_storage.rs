@@ -1,11 +1,21 @@
-use crate::component::error::ComponentWriteError;
-use crate::component::{Component, ComponentStorage};
+use crate::component::disjoint::get_many_slots_mut;
+use crate::component::entry::{Entry, InsertVacant, OccupiedEntry, VacantEntry, VacantSlot};
+use crate::component::error::{ComponentWriteError, ComponentWriteErrorKind, GetManyMutError};
+use crate::component::memory_usage::{vec_heap_bytes, MemoryUsage};
+use crate::component::ordered::{
+    first_of_slots, last_of_slots, next_of_slots_after, prev_of_slots_before, range_of_slots,
+};
+use crate::component::{
+    Component, ComponentIter, ComponentIterMut, ComponentStorage, OrderedComponentStorage,
+};
 use std::mem;
+use std::ops::Range;
 
 /// an implementation of `ComponentStorage` the uses resizable vectors as backing memory
-#[derive(Debug, Default)]
+#[derive(Debug)]
 pub struct VecComponentStorage<T: Component> {
     components: Vec<Option<T>>,
+    len: usize,
 }
 
 impl<T> VecComponentStorage<T>
@@ -17,6 +27,7 @@ where
     pub fn new_with_initial_size(initial_size: usize) -> Self {
         Self {
             components: Vec::with_capacity(initial_size),
+            len: 0,
         }
     }
 
@@ -40,6 +51,50 @@ where
         None
     }
 
+    fn get_mut(&mut self, index: usize) -> Option<&'_ mut T> {
+        if let Some(Some(component)) = self.components.get_mut(index) {
+            return Some(component);
+        }
+        None
+    }
+
+    fn get_many_mut_into<'a>(
+        &'a mut self,
+        indices: &[usize],
+        components: &mut [Option<&'a mut T>],
+    ) -> Result<(), GetManyMutError> {
+        // SAFETY: each index is its own slot
+        unsafe {
+            get_many_slots_mut(
+                &mut self.components,
+                indices,
+                components,
+                Some,
+                Option::as_mut,
+            )
+        }
+    }
+
+    unsafe fn get_unchecked(&self, index: usize) -> &T {
+        // SAFETY: the caller guarantees a component is stored at the index
+        unsafe {
+            self.components
+                .get_unchecked(index)
+                .as_ref()
+                .unwrap_unchecked()
+        }
+    }
+
+    unsafe fn get_unchecked_mut(&mut self, index: usize) -> &mut T {
+        // SAFETY: the caller guarantees a component is stored at the index
+        unsafe {
+            self.components
+                .get_unchecked_mut(index)
+                .as_mut()
+                .unwrap_unchecked()
+        }
+    }
+
     fn insert(&mut self, index: usize, component: T) -> Result<Option<T>, ComponentWriteError> {
         if index >= self.components.len() {
             self.resize(index + 1)
@@ -47,11 +102,16 @@ where
         if let Some(stored) = self.components.get_mut(index) {
             let mut tmp = Some(component);
             mem::swap(stored, &mut tmp);
+            if tmp.is_none() {
+                self.len += 1;
+            }
             return Ok(tmp);
         }
-        Err(ComponentWriteError::new_with_detail::<T>(
+        Err(ComponentWriteError::new_with_kind::<T>(
             index,
-            "index out of bounds",
+            ComponentWriteErrorKind::OutOfBounds {
+                capacity: self.components.len(),
+            },
         ))
     }
 
@@ -59,30 +119,190 @@ where
         if let Some(component) = self.components.get_mut(index) {
             let mut tmp = None;
             mem::swap(component, &mut tmp);
+            if tmp.is_some() {
+                self.len -= 1;
+            }
             return Ok(tmp);
         }
         Ok(None) // index out of bounds, but that's okay because we're "deleting" it
     }
+
+    fn entry(&mut self, index: usize) -> Result<Entry<'_, T>, ComponentWriteError> {
+        if index >= self.components.len() {
+            return Ok(Entry::Vacant(VacantEntry::new(index, self)));
+        }
+        let len = &mut self.len;
+        Ok(match &mut self.components[index] {
+            Some(component) => Entry::Occupied(OccupiedEntry::new(index, component)),
+            slot @ None => Entry::Vacant(VacantEntry::with_slot(
+                index,
+                VacantSlot::Option {
+                    slot,
+                    len,
+                    page_len: None,
+                },
+            )),
+        })
+    }
+
+    fn iter(&self) -> ComponentIter<'_, T> {
+        Box::new(
+            self.components
+                .iter()
+                .enumerate()
+                .filter_map(|(index, component)| Some((index, component.as_ref()?))),
+        )
+    }
+
+    fn iter_mut(&mut self) -> ComponentIterMut<'_, T> {
+        Box::new(
+            self.components
+                .iter_mut()
+                .enumerate()
+                .filter_map(|(index, component)| Some((index, component.as_mut()?))),
+        )
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn clear(&mut self) {
+        self.components.clear();
+        self.len = 0;
+    }
+
+    fn capacity(&self) -> usize {
+        self.components.capacity()
+    }
+
+    fn reserve(&mut self, additional: usize) {
+        self.components.reserve(additional);
+    }
+
+    fn shrink_to_fit(&mut self) {
+        let used_length = self
+            .components
+            .iter()
+            .rposition(Option::is_some)
+            .map_or(0, |last| last + 1);
+        self.components.truncate(used_length);
+        self.components.shrink_to_fit();
+    }
+
+    fn memory_usage(&self) -> MemoryUsage {
+        MemoryUsage::new::<Self>(vec_heap_bytes::<Option<T>>(self.components.capacity()))
+    }
+
+    fn extend(
+        &mut self,
+        components: &mut dyn Iterator<Item = (usize, T)>,
+    ) -> Result<(), ComponentWriteError> {
+        let components: Vec<(usize, T)> = components.collect();
+        if let Some(max_index) = components.iter().map(|(index, _)| *index).max() {
+            self.resize(max_index + 1);
+        }
+        for (index, component) in components {
+            if self.components[index].replace(component).is_none() {
+                self.len += 1;
+            }
+        }
+        Ok(())
+    }
+
+    fn retain(&mut self, predicate: &mut dyn FnMut(usize, &mut T) -> bool) {
+        for (index, stored) in self.components.iter_mut().enumerate() {
+            if let Some(component) = stored {
+                if !predicate(index, component) {
+                    *stored = None;
+                    self.len -= 1;
+                }
+            }
+        }
+    }
+}
+
+impl<T> OrderedComponentStorage<T> for VecComponentStorage<T>
+where
+    T: Component,
+{
+    fn range(&self, range: Range<usize>) -> ComponentIter<'_, T> {
+        range_of_slots(&self.components, range)
+    }
+
+    fn first(&self) -> Option<(usize, &T)> {
+        first_of_slots(&self.components)
+    }
+
+    fn last(&self) -> Option<(usize, &T)> {
+        last_of_slots(&self.components)
+    }
+
+    fn next_after(&self, index: usize) -> Option<(usize, &T)> {
+        next_of_slots_after(&self.components, index)
+    }
+
+    fn prev_before(&self, index: usize) -> Option<(usize, &T)> {
+        prev_of_slots_before(&self.components, index)
+    }
+}
+
+impl<T> InsertVacant<T> for VecComponentStorage<T>
+where
+    T: Component,
+{
+    fn insert_vacant(&mut self, index: usize, component: T) -> &mut T {
+        // only entries beyond the end of the vector are left to the storage
+        self.components.resize_with(index + 1, || None);
+        self.len += 1;
+        self.components[index].insert(component)
+    }
+}
+
+impl<T> Default for VecComponentStorage<T>
+where
+    T: Component,
+{
+    fn default() -> Self {
+        Self {
+            components: Default::default(),
+            len: 0,
+        }
+    }
 }
 
 #[cfg(test)]
 mod test {
-    use crate::component::test::{
-        test_init_behavior, test_insert_and_update_behavior, TestComponent,
-    };
+    use crate::component::testing::{test_conformance, test_ordered_behavior, TestComponent};
     use crate::component::vec_component_storage::VecComponentStorage;
     use crate::component::ComponentStorage;
+    use std::mem::size_of;
 
     #[test]
-    fn test_init() {
-        let storage: VecComponentStorage<TestComponent> = VecComponentStorage::default();
-        test_init_behavior(&storage, 0..64);
+    fn test_conformance_suite() {
+        test_conformance(VecComponentStorage::default, None);
+    }
+
+    #[test]
+    fn test_ordered() {
+        let mut storage: VecComponentStorage<TestComponent> = VecComponentStorage::default();
+        test_ordered_behavior(&mut storage);
     }
 
     #[test]
-    fn test_insert_update() {
+    fn test_shrink_after_spike() {
         let mut storage: VecComponentStorage<TestComponent> = VecComponentStorage::default();
-        test_insert_and_update_behavior(&mut storage, 0..64);
+        assert!(storage.insert(3, TestComponent(3)).is_ok());
+        assert!(storage.insert(100_000, TestComponent(100_000)).is_ok());
+        assert!(storage.delete(100_000).is_ok());
+        storage.shrink_to_fit();
+        assert!(
+            storage.capacity() < 100_000,
+            "capacity was {} after shrinking",
+            storage.capacity()
+        );
+        assert_eq!(storage.get(3), Some(&TestComponent(3)));
+        assert_eq!(storage.len(), 1);
     }
 
     #[test]
@@ -117,4 +337,20 @@ mod test {
             ),
         }
     }
+
+    #[test]
+    fn test_memory_usage() {
+        let mut storage: VecComponentStorage<TestComponent> = VecComponentStorage::default();
+        assert_eq!(storage.memory_usage().heap_bytes, 0);
+        storage.reserve(100);
+        let usage = storage.memory_usage();
+        assert_eq!(
+            usage.heap_bytes,
+            storage.capacity() * size_of::<Option<TestComponent>>()
+        );
+        assert_eq!(
+            usage.inline_bytes,
+            size_of::<VecComponentStorage<TestComponent>>()
+        );
+    }
 }
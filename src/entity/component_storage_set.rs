@@ -1,16 +1,144 @@
-use crate::component::{Component, ComponentStorage};
-use std::any::{Any, TypeId};
+use crate::component::error::{ComponentWriteError, ComponentWriteErrorKind};
+use crate::component::memory_usage::{hash_map_heap_bytes, MemoryUsage};
+use crate::component::{Component, ComponentStorage, DefaultStorage};
+use std::any::{type_name, Any, TypeId};
 use std::collections::HashMap;
+use std::fmt::{Debug, Formatter};
+use std::mem::size_of;
 
 #[cfg(test)]
-use crate::component::test::TestComponent;
+use crate::component::array_component_storage::ArrayComponentStorage;
+#[cfg(test)]
+use crate::component::hash_map_component_storage::HashMapComponentStorage;
+#[cfg(test)]
+use crate::component::testing::TestComponent;
 #[cfg(test)]
 use crate::component::vec_component_storage::VecComponentStorage;
 
+/// a `ComponentStorage` with its component type erased, so that storages for different
+/// component types can be kept together, and operations that don't need to know the type (such
+/// as despawning an entity or dumping its components) can run across all of them
+pub trait ErasedComponentStorage {
+    /// get the name of the component type
+    fn component_type(&self) -> &'static str;
+
+    /// get the `TypeId` of the component type
+    fn component_type_id(&self) -> TypeId;
+
+    /// get the number of components stored
+    fn len(&self) -> usize;
+
+    /// check whether no components are stored
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// check whether a component is stored at an index (entity ID)
+    fn contains(&self, index: usize) -> bool;
+
+    /// delete (and drop) the component at an index (entity ID), returning whether there was one
+    fn delete(&mut self, index: usize) -> Result<bool, ComponentWriteError>;
+
+    /// get the component at an index (entity ID) as something that can be debug-printed
+    fn debug_component(&self, index: usize) -> Option<&dyn Debug>;
+
+    /// report how much memory the storage is using
+    fn memory_usage(&self) -> MemoryUsage;
+
+    /// get the storage as `Any`, to downcast it back to `Box<dyn ComponentStorage<T>>`
+    fn as_any(&self) -> &dyn Any;
+
+    /// get the storage as `Any` mutably, to downcast it back to `Box<dyn ComponentStorage<T>>`
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+
+    /// convert the storage into `Any`, to downcast it back to `Box<dyn ComponentStorage<T>>`
+    fn into_any(self: Box<Self>) -> Box<dyn Any>;
+}
+
+impl<T> ErasedComponentStorage for Box<dyn ComponentStorage<T>>
+where
+    T: 'static + Component,
+{
+    fn component_type(&self) -> &'static str {
+        type_name::<T>()
+    }
+
+    fn component_type_id(&self) -> TypeId {
+        TypeId::of::<T>()
+    }
+
+    fn len(&self) -> usize {
+        self.as_ref().len()
+    }
+
+    fn contains(&self, index: usize) -> bool {
+        self.as_ref().contains(index)
+    }
+
+    fn delete(&mut self, index: usize) -> Result<bool, ComponentWriteError> {
+        Ok(self.as_mut().delete(index)?.is_some())
+    }
+
+    fn debug_component(&self, index: usize) -> Option<&dyn Debug> {
+        Some(self.as_ref().get(index)?)
+    }
+
+    fn memory_usage(&self) -> MemoryUsage {
+        self.as_ref().memory_usage()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn into_any(self: Box<Self>) -> Box<dyn Any> {
+        self
+    }
+}
+
+impl Debug for dyn ErasedComponentStorage {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "ComponentStorage<{}>", self.component_type())
+    }
+}
+
+/// the memory used by the `ComponentStorage` for one component type
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ComponentMemoryUsage {
+    /// the name of the component type
+    pub component_type: &'static str,
+    /// the memory used by the storage (its inline bytes live in the box the set keeps it in)
+    pub usage: MemoryUsage,
+}
+
+/// the memory used by a `ComponentStorageSet`, broken down by component type
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ComponentStorageSetMemoryUsage {
+    /// the memory used by each storage, sorted by component type name
+    pub storages: Vec<ComponentMemoryUsage>,
+    /// the memory used by the set itself to keep track of the storages
+    pub overhead: MemoryUsage,
+}
+
+impl ComponentStorageSetMemoryUsage {
+    /// get the combined memory used by the set and every storage in it
+    pub fn total(&self) -> MemoryUsage {
+        self.storages
+            .iter()
+            .map(|storage| storage.usage)
+            .sum::<MemoryUsage>()
+            + self.overhead
+    }
+}
+
 /// a collection to store the different `ComponentStorage`s for different component types
 #[derive(Debug, Default)]
 pub struct ComponentStorageSet {
-    component_storages: HashMap<TypeId, Box<dyn Any>>,
+    component_storages: HashMap<TypeId, Box<dyn ErasedComponentStorage>>,
 }
 
 impl ComponentStorageSet {
@@ -22,12 +150,17 @@ impl ComponentStorageSet {
         storage: S,
     ) -> Option<Box<dyn ComponentStorage<T>>> {
         let boxed_trait: Box<dyn ComponentStorage<T>> = Box::new(storage);
-        let boxed_any: Box<dyn Any> = Box::new(boxed_trait);
+        let boxed_erased: Box<dyn ErasedComponentStorage> = Box::new(boxed_trait);
 
-        let prev = self.component_storages.insert(TypeId::of::<T>(), boxed_any);
+        let prev = self
+            .component_storages
+            .insert(TypeId::of::<T>(), boxed_erased);
         match prev {
             Some(prev_storage) => {
-                if let Ok(s) = prev_storage.downcast::<Box<dyn ComponentStorage<T>>>() {
+                if let Ok(s) = prev_storage
+                    .into_any()
+                    .downcast::<Box<dyn ComponentStorage<T>>>()
+                {
                     return Some(*s);
                 }
                 None
@@ -42,7 +175,10 @@ impl ComponentStorageSet {
     ) -> Option<&dyn ComponentStorage<T>> {
         let type_id = TypeId::of::<T>();
         if let Some(storage) = self.component_storages.get(&type_id) {
-            if let Some(s) = storage.downcast_ref::<Box<dyn ComponentStorage<T>>>() {
+            if let Some(s) = storage
+                .as_any()
+                .downcast_ref::<Box<dyn ComponentStorage<T>>>()
+            {
                 return Some(s.as_ref());
             }
         }
@@ -55,14 +191,164 @@ impl ComponentStorageSet {
     ) -> Option<&mut dyn ComponentStorage<T>> {
         let type_id = TypeId::of::<T>();
         if let Some(storage) = self.component_storages.get_mut(&type_id) {
-            if let Some(s) = storage.downcast_mut::<Box<dyn ComponentStorage<T>>>() {
+            if let Some(s) = storage
+                .as_any_mut()
+                .downcast_mut::<Box<dyn ComponentStorage<T>>>()
+            {
                 return Some(s.as_mut());
             }
         }
         None
     }
+
+    /// get the type-erased `ComponentStorage` for a component type, given its `TypeId`
+    pub fn get_erased_storage_ref(&self, type_id: TypeId) -> Option<&dyn ErasedComponentStorage> {
+        Some(self.component_storages.get(&type_id)?.as_ref())
+    }
+
+    /// get the type-erased `ComponentStorage` for a component type mutably, given its `TypeId`
+    pub fn get_erased_storage_mut(
+        &mut self,
+        type_id: TypeId,
+    ) -> Option<&mut dyn ErasedComponentStorage> {
+        Some(self.component_storages.get_mut(&type_id)?.as_mut())
+    }
+
+    /// iterate over every stored `ComponentStorage`, type-erased (in no particular order)
+    pub fn erased_storages(&self) -> impl Iterator<Item = &dyn ErasedComponentStorage> {
+        self.component_storages
+            .values()
+            .map(|storage| storage.as_ref())
+    }
+
+    /// iterate mutably over every stored `ComponentStorage`, type-erased (in no particular
+    /// order)
+    pub fn erased_storages_mut(
+        &mut self,
+    ) -> impl Iterator<Item = &mut (dyn ErasedComponentStorage + 'static)> {
+        self.component_storages
+            .values_mut()
+            .map(|storage| storage.as_mut())
+    }
+
+    /// get every component stored at an index (entity ID) as something that can be
+    /// debug-printed, along with the name of its type (sorted by type name)
+    pub fn debug_components(&self, index: usize) -> Vec<(&'static str, &dyn Debug)> {
+        let mut components: Vec<(&'static str, &dyn Debug)> = self
+            .erased_storages()
+            .filter_map(|storage| Some((storage.component_type(), storage.debug_component(index)?)))
+            .collect();
+        components.sort_by_key(|(component_type, _)| *component_type);
+        components
+    }
+
+    /// delete the component at an index (entity ID) from every stored `ComponentStorage`,
+    /// returning how many components were deleted
+    ///
+    /// storages that can't hold the index (e.g. fixed-size storages it's past the end of) are
+    /// skipped, since they can't have a component for it. any other error stops the deletion
+    /// part way through, leaving the components in the remaining storages in place
+    pub fn delete_components(&mut self, index: usize) -> Result<usize, ComponentWriteError> {
+        let mut deleted = 0;
+        for storage in self.erased_storages_mut() {
+            match storage.delete(index) {
+                Ok(true) => deleted += 1,
+                Ok(false) => {}
+                Err(err) if matches!(err.kind(), ComponentWriteErrorKind::OutOfBounds { .. }) => {}
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(deleted)
+    }
+
+    /// delete the component at an index (entity ID) from every stored `ComponentStorage`,
+    /// returning how many components were deleted
+    ///
+    /// unlike `delete_components`, a storage that fails doesn't stop the others from being
+    /// tried; the first error is returned once every storage has been tried
+    pub fn purge_components(&mut self, index: usize) -> Result<usize, ComponentWriteError> {
+        let mut deleted = 0;
+        let mut first_err = None;
+        for storage in self.erased_storages_mut() {
+            match storage.delete(index) {
+                Ok(true) => deleted += 1,
+                Ok(false) => {}
+                Err(err) if matches!(err.kind(), ComponentWriteErrorKind::OutOfBounds { .. }) => {}
+                Err(err) => {
+                    first_err.get_or_insert(err);
+                }
+            }
+        }
+        match first_err {
+            Some(err) => Err(err),
+            None => Ok(deleted),
+        }
+    }
+
+    /// report how much memory every stored `ComponentStorage` is using, along with the memory
+    /// used to keep track of them
+    pub fn memory_usage(&self) -> ComponentStorageSetMemoryUsage {
+        let mut storages: Vec<ComponentMemoryUsage> = self
+            .component_storages
+            .values()
+            .map(|storage| ComponentMemoryUsage {
+                component_type: storage.component_type(),
+                usage: storage.memory_usage(),
+            })
+            .collect();
+        storages.sort_by_key(|storage| storage.component_type);
+        // each storage is boxed as a `ComponentStorage` (counted as the storage's inline bytes),
+        // and that box is boxed again so it can be kept alongside other component types
+        let boxes = self.component_storages.len() * size_of::<Box<dyn Any>>();
+        ComponentStorageSetMemoryUsage {
+            storages,
+            overhead: MemoryUsage::new::<Self>(
+                hash_map_heap_bytes::<TypeId, Box<dyn ErasedComponentStorage>>(
+                    self.component_storages.capacity(),
+                ) + boxes,
+            ),
+        }
+    }
+}
+
+/// a set of component types that declare their default storage, which can be registered
+/// together (implemented for tuples of up to 12 `DefaultStorage` component types)
+pub trait ComponentSet {
+    /// store a new, empty default storage for every component type in the set (overwriting
+    /// any storages already stored for them)
+    fn insert_default_storages(component_storage_set: &mut ComponentStorageSet);
 }
 
+macro_rules! impl_component_set {
+    ($($component:ident),+) => {
+        impl<$($component),+> ComponentSet for ($($component,)+)
+        where
+            $($component: 'static + DefaultStorage, $component::Storage: 'static,)+
+        {
+            fn insert_default_storages(component_storage_set: &mut ComponentStorageSet) {
+                $(
+                    component_storage_set.insert_component_storage::<$component, _>(
+                        <$component::Storage as Default>::default(),
+                    );
+                )+
+            }
+        }
+    };
+}
+
+impl_component_set!(A);
+impl_component_set!(A, B);
+impl_component_set!(A, B, C);
+impl_component_set!(A, B, C, D);
+impl_component_set!(A, B, C, D, E);
+impl_component_set!(A, B, C, D, E, F);
+impl_component_set!(A, B, C, D, E, F, G);
+impl_component_set!(A, B, C, D, E, F, G, H);
+impl_component_set!(A, B, C, D, E, F, G, H, I);
+impl_component_set!(A, B, C, D, E, F, G, H, I, J);
+impl_component_set!(A, B, C, D, E, F, G, H, I, J, K);
+impl_component_set!(A, B, C, D, E, F, G, H, I, J, K, L);
+
 #[test]
 fn can_store_and_retrieve_component_storages() {
     let mut component_storage_set: ComponentStorageSet = Default::default();
@@ -80,3 +366,189 @@ fn can_store_and_retrieve_component_storages() {
         "entity storage returned None when a storage known to exist was requested."
     );
 }
+
+#[test]
+fn can_modify_components_through_mutable_storage() {
+    let mut component_storage_set: ComponentStorageSet = Default::default();
+    component_storage_set.insert_component_storage(VecComponentStorage::<TestComponent>::default());
+    if let Some(storage) = component_storage_set.get_component_storage_mut::<TestComponent>() {
+        assert!(storage.insert(0, TestComponent(1)).is_ok());
+        if let Some(component) = storage.get_mut(0) {
+            component.0 = 2;
+        }
+    }
+    assert_eq!(
+        component_storage_set
+            .get_component_storage_ref::<TestComponent>()
+            .and_then(|storage| storage.get(0)),
+        Some(&TestComponent(2)),
+        "change made through get_mut was not persisted"
+    );
+}
+
+#[cfg(test)]
+#[derive(Debug)]
+struct Score(#[allow(dead_code)] u32);
+
+#[cfg(test)]
+impl Component for Score {}
+
+#[cfg(test)]
+impl DefaultStorage for Score {
+    type Storage = VecComponentStorage<Score>;
+}
+
+#[cfg(test)]
+#[derive(Debug)]
+struct Rank(#[allow(dead_code)] u8);
+
+#[cfg(test)]
+impl Component for Rank {}
+
+#[cfg(test)]
+impl DefaultStorage for Rank {
+    type Storage = HashMapComponentStorage<Rank>;
+}
+
+#[test]
+fn can_insert_default_storages_for_component_set() {
+    let mut component_storage_set: ComponentStorageSet = Default::default();
+    <(Score, Rank)>::insert_default_storages(&mut component_storage_set);
+    assert!(
+        component_storage_set
+            .get_component_storage_ref::<Score>()
+            .is_some(),
+        "no storage was stored for a component type in the set"
+    );
+    assert!(
+        component_storage_set
+            .get_component_storage_ref::<Rank>()
+            .is_some(),
+        "no storage was stored for a component type in the set"
+    );
+}
+
+#[test]
+fn can_delete_components_from_every_storage() {
+    let mut component_storage_set: ComponentStorageSet = Default::default();
+    <(Score, Rank)>::insert_default_storages(&mut component_storage_set);
+    component_storage_set
+        .insert_component_storage(ArrayComponentStorage::<TestComponent, 4>::default());
+    for index in [2, 7] {
+        if let Some(storage) = component_storage_set.get_component_storage_mut::<Score>() {
+            assert!(storage.insert(index, Score(1)).is_ok());
+        }
+        if let Some(storage) = component_storage_set.get_component_storage_mut::<Rank>() {
+            assert!(storage.insert(index, Rank(1)).is_ok());
+        }
+    }
+    if let Some(storage) = component_storage_set.get_component_storage_mut::<TestComponent>() {
+        assert!(storage.insert(2, TestComponent(1)).is_ok());
+    }
+
+    match component_storage_set.delete_components(2) {
+        Ok(deleted) => assert_eq!(deleted, 3, "deleted {} components", deleted),
+        Err(err) => panic!("deleting components returned error {:?}", err),
+    }
+    match component_storage_set.delete_components(7) {
+        Ok(deleted) => assert_eq!(deleted, 2, "deleted {} components", deleted),
+        Err(err) => panic!(
+            "deleting past the end of a fixed-size storage returned error {:?}",
+            err
+        ),
+    }
+    assert_eq!(component_storage_set.delete_components(2).ok(), Some(0));
+    assert!(component_storage_set
+        .get_component_storage_ref::<Score>()
+        .is_some_and(|storage| storage.is_empty()));
+    assert!(component_storage_set
+        .get_component_storage_ref::<Rank>()
+        .is_some_and(|storage| storage.is_empty()));
+}
+
+#[test]
+fn can_inspect_storages_without_their_component_types() {
+    let mut component_storage_set: ComponentStorageSet = Default::default();
+    <(Score, Rank)>::insert_default_storages(&mut component_storage_set);
+    if let Some(storage) = component_storage_set.get_component_storage_mut::<Score>() {
+        assert!(storage.insert(3, Score(40)).is_ok());
+        assert!(storage.insert(4, Score(50)).is_ok());
+    }
+    if let Some(storage) = component_storage_set.get_component_storage_mut::<Rank>() {
+        assert!(storage.insert(3, Rank(2)).is_ok());
+    }
+
+    let Some(storage) = component_storage_set.get_erased_storage_ref(TypeId::of::<Score>()) else {
+        panic!("no type-erased storage was found for a registered component type");
+    };
+    assert_eq!(storage.component_type(), type_name::<Score>());
+    assert_eq!(storage.component_type_id(), TypeId::of::<Score>());
+    assert_eq!(storage.len(), 2);
+    assert!(storage.contains(4));
+    assert!(!storage.contains(5));
+    assert_eq!(
+        storage
+            .debug_component(4)
+            .map(|component| format!("{:?}", component)),
+        Some(String::from("Score(50)"))
+    );
+    assert!(storage.debug_component(5).is_none());
+    assert!(component_storage_set
+        .get_erased_storage_ref(TypeId::of::<TestComponent>())
+        .is_none());
+
+    let dump: Vec<String> = component_storage_set
+        .debug_components(3)
+        .iter()
+        .map(|(component_type, component)| format!("{}: {:?}", component_type, component))
+        .collect();
+    assert_eq!(
+        dump,
+        [
+            format!("{}: Rank(2)", type_name::<Rank>()),
+            format!("{}: Score(40)", type_name::<Score>()),
+        ]
+    );
+
+    if let Some(storage) = component_storage_set.get_erased_storage_mut(TypeId::of::<Score>()) {
+        assert!(matches!(storage.delete(4), Ok(true)));
+        assert!(matches!(storage.delete(4), Ok(false)));
+    }
+    assert_eq!(
+        component_storage_set
+            .erased_storages()
+            .map(|storage| storage.len())
+            .sum::<usize>(),
+        2
+    );
+}
+
+#[test]
+fn reports_memory_usage_for_each_component_type() {
+    let mut component_storage_set: ComponentStorageSet = Default::default();
+    <(Score, Rank)>::insert_default_storages(&mut component_storage_set);
+    let usage = component_storage_set.memory_usage();
+    assert_eq!(
+        usage
+            .storages
+            .iter()
+            .map(|storage| storage.component_type)
+            .collect::<Vec<_>>(),
+        vec![type_name::<Rank>(), type_name::<Score>()],
+        "storages were not reported by component type name"
+    );
+    assert!(usage.overhead.heap_bytes > 0);
+
+    let storage = component_storage_set
+        .get_component_storage_mut::<Score>()
+        .unwrap();
+    storage.reserve(100);
+    let reserved = storage.memory_usage();
+    let usage = component_storage_set.memory_usage();
+    assert_eq!(usage.storages[1].usage, reserved);
+    assert!(reserved.heap_bytes >= 100 * size_of::<Option<Score>>());
+    assert_eq!(
+        usage.total().total(),
+        usage.storages[0].usage.total() + reserved.total() + usage.overhead.total()
+    );
+}
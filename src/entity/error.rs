@@ -0,0 +1,115 @@
+//! errors associated with managing entities
+
+use crate::component::error::ComponentWriteError;
+use crate::entity::Entity;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+
+/// an error representing a failure to spawn an entity with its components (after which
+/// nothing from the attempt is left in the world)
+#[derive(Debug)]
+pub enum SpawnError {
+    /// no entity could be provisioned (e.g. the depot was full)
+    Provision(String),
+    /// one of the entity's components couldn't be stored
+    Component(ComponentWriteError),
+    /// one of the entity's components couldn't be stored, and undoing the spawn failed too
+    /// (the entity is left in the world, so that despawning it can be retried)
+    Rollback {
+        /// the error that stopped the spawn
+        error: ComponentWriteError,
+        /// the error that stopped the spawn from being undone (boxed, since it's rare and would
+        /// double the size of every `SpawnError`)
+        rollback: Box<DespawnError>,
+        /// the entity left in the world
+        entity: Entity,
+    },
+}
+
+impl Display for SpawnError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SpawnError::Provision(detail) => {
+                write!(f, "Failed to provision an entity to spawn: {}", detail)
+            }
+            SpawnError::Component(err) => write!(f, "Failed to spawn an entity: {}", err),
+            SpawnError::Rollback {
+                error, rollback, ..
+            } => write!(
+                f,
+                "Failed to spawn an entity: {} (and failed to undo the spawn: {})",
+                error, rollback
+            ),
+        }
+    }
+}
+
+impl Error for SpawnError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            SpawnError::Provision(_) => None,
+            SpawnError::Component(err) | SpawnError::Rollback { error: err, .. } => Some(err),
+        }
+    }
+}
+
+/// an error representing a failure to despawn an entity
+#[derive(Debug)]
+pub enum DespawnError {
+    /// the entity handle is stale (its entity was already despawned)
+    StaleEntity,
+    /// one of the entity's components couldn't be deleted
+    Component(ComponentWriteError),
+    /// the entity's index couldn't be freed in the depot
+    Depot(String),
+}
+
+impl Display for DespawnError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DespawnError::StaleEntity => {
+                write!(f, "Failed to despawn an entity: stale entity handle")
+            }
+            DespawnError::Component(err) => write!(f, "Failed to despawn an entity: {}", err),
+            DespawnError::Depot(detail) => {
+                write!(f, "Failed to free a despawned entity's index: {}", detail)
+            }
+        }
+    }
+}
+
+impl Error for DespawnError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            DespawnError::Component(err) => Some(err),
+            DespawnError::StaleEntity | DespawnError::Depot(_) => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::component::error::{ComponentWriteError, ComponentWriteErrorKind};
+    use crate::component::testing::TestComponent;
+    use crate::entity::error::SpawnError;
+    use std::error::Error;
+
+    #[test]
+    fn test_source() {
+        let err = SpawnError::Component(ComponentWriteError::new_with_kind::<TestComponent>(
+            3,
+            ComponentWriteErrorKind::StorageNotRegistered,
+        ));
+        match err.source() {
+            Some(source) => assert_eq!(
+                source.to_string(),
+                "Failed to write `wyrd::component::testing::TestComponent` component for \
+                 entity 3: component storage not found."
+            ),
+            None => panic!("the component error was not returned as the source"),
+        }
+        assert!(SpawnError::Provision(String::from("full"))
+            .source()
+            .is_none());
+    }
+}
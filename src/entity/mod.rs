@@ -1,42 +1,243 @@
 //! code associated with managing and composing entities
-use crate::component::error::ComponentWriteError;
+use crate::component::entry::Entry;
+use crate::component::error::{ComponentWriteError, ComponentWriteErrorKind};
+use crate::component::memory_usage::{hash_map_heap_bytes, vec_heap_bytes, MemoryUsage};
 use crate::component::{Component, ComponentStorage};
-use crate::entity::component_storage_set::ComponentStorageSet;
-use armory::Depot;
+use crate::entity::component_storage_set::{
+    ComponentSet, ComponentStorageSet, ComponentStorageSetMemoryUsage,
+};
+use crate::entity::error::{DespawnError, SpawnError};
+use armory::space::Space;
+use armory::{ArrayDepot, Depot, VecDepot};
 use std::collections::HashMap;
+use std::fmt::Debug;
 use std::hash::Hash;
 
 pub mod component_storage_set;
+pub mod error;
+
+/// a handle to an entity: its index, along with the generation of that index when the entity
+/// was provisioned
+///
+/// indices are reused once their entity is despawned, and each reuse starts a new generation,
+/// so a `World` can tell when a handle kept around for too long refers to an entity that no
+/// longer exists (rather than to whichever entity has the index now)
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Entity {
+    index: usize,
+    generation: u32,
+}
+
+impl Entity {
+    /// get the entity's index (the index of its components in every `ComponentStorage`)
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// get the generation of the entity's index when the entity was provisioned
+    pub fn generation(&self) -> u32 {
+        self.generation
+    }
+}
 
 pub struct EntityProvision<ID: Clone + Eq + Hash> {
-    pub index: usize,
+    pub entity: Entity,
     pub id: ID,
 }
 
-pub struct Entity<'a, ID: Clone + Eq + Hash, EntDepot: Depot<ID>, IDGen: Fn() -> ID> {
-    pub index: usize,
+/// a `Depot` that can report how much memory it uses
+///
+/// depots don't expose their size, so the caller passes in how many slots have been used (the
+/// highest index ever provisioned plus one)
+pub trait DepotMemoryUsage {
+    /// report how much memory the depot uses after `slot_count` slots have been used
+    fn memory_usage(&self, slot_count: usize) -> MemoryUsage;
+}
+
+impl<T> DepotMemoryUsage for VecDepot<T> {
+    fn memory_usage(&self, slot_count: usize) -> MemoryUsage {
+        // armory doesn't expose the vector inside a `VecDepot`, but it only ever grows by pushing
+        // one slot at a time, so its capacity follows from the slot count: std's `Vec` starts at
+        // a minimum capacity that depends on the element size and doubles from there
+        let min_capacity = match size_of::<Space<T>>() {
+            1 => 8,
+            size if size <= 1024 => 4,
+            _ => 1,
+        };
+        let capacity = match slot_count {
+            0 => 0,
+            slot_count => slot_count.next_power_of_two().max(min_capacity),
+        };
+        MemoryUsage::new::<Self>(vec_heap_bytes::<Space<T>>(capacity))
+    }
+}
+
+impl<T, const SIZE: usize> DepotMemoryUsage for ArrayDepot<T, SIZE> {
+    fn memory_usage(&self, _slot_count: usize) -> MemoryUsage {
+        // every slot lives inside the depot itself
+        MemoryUsage::new::<Self>(0)
+    }
+}
+
+/// the memory used by a `World`, broken down into its parts
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct WorldMemoryUsage {
+    /// the memory used by the storage for each component type
+    pub components: ComponentStorageSetMemoryUsage,
+    /// the memory used to map entity IDs to entity indices
+    pub id_to_index: MemoryUsage,
+    /// the memory used to keep track of the generation of each entity index
+    pub generations: MemoryUsage,
+    /// the memory used by the `Depot` that hands out entity indices
+    pub ids: MemoryUsage,
+}
+
+impl WorldMemoryUsage {
+    /// get the combined memory used by every part of the world
+    pub fn total(&self) -> MemoryUsage {
+        self.components.total() + self.id_to_index + self.generations + self.ids
+    }
+}
+
+/// a view of a live entity in a `World`, for reading its components
+pub struct EntityRef<'a, ID: Clone + Eq + Hash, EntDepot: Depot<ID>, IDGen: Fn() -> ID> {
+    entity: Entity,
     world: &'a World<ID, EntDepot, IDGen>,
 }
 
-impl<'a, ID, EntDepot, IDGen> Entity<'a, ID, EntDepot, IDGen>
+impl<'a, ID, EntDepot, IDGen> EntityRef<'a, ID, EntDepot, IDGen>
 where
     ID: Clone + Eq + Hash,
     EntDepot: Depot<ID>,
     IDGen: Fn() -> ID,
 {
+    /// get the handle of the entity
+    pub fn entity(&self) -> Entity {
+        self.entity
+    }
+
+    pub fn get_component<T: 'static + Component>(&self) -> Option<&'a T> {
+        if let Some(storage) = self
+            .world
+            .component_storage_set
+            .get_component_storage_ref::<T>()
+        {
+            return storage.get(self.entity.index);
+        }
+
+        None
+    }
+
+    /// get every component the entity has as something that can be debug-printed, along with
+    /// the name of its type (sorted by type name)
+    pub fn debug_components(&self) -> Vec<(&'static str, &'a dyn Debug)> {
+        self.world
+            .component_storage_set
+            .debug_components(self.entity.index)
+    }
+}
+
+/// a view of a live entity in a `World`, for reading and modifying its components
+pub struct EntityMut<'a, ID: Clone + Eq + Hash, EntDepot: Depot<ID>, IDGen: Fn() -> ID> {
+    entity: Entity,
+    world: &'a mut World<ID, EntDepot, IDGen>,
+}
+
+impl<'a, ID, EntDepot, IDGen> EntityMut<'a, ID, EntDepot, IDGen>
+where
+    ID: Clone + Eq + Hash,
+    EntDepot: Depot<ID>,
+    IDGen: Fn() -> ID,
+{
+    /// get the handle of the entity
+    pub fn entity(&self) -> Entity {
+        self.entity
+    }
+
     pub fn get_component<T: 'static + Component>(&self) -> Option<&T> {
         if let Some(storage) = self
             .world
             .component_storage_set
             .get_component_storage_ref::<T>()
         {
-            return storage.get(self.index);
+            return storage.get(self.entity.index);
+        }
+
+        None
+    }
+
+    pub fn get_component_mut<T: 'static + Component>(&mut self) -> Option<&mut T> {
+        if let Some(storage) = self
+            .world
+            .component_storage_set
+            .get_component_storage_mut::<T>()
+        {
+            return storage.get_mut(self.entity.index);
         }
 
         None
     }
 }
 
+/// a component waiting to be stored for an entity that an `EntityBuilder` is spawning
+type PendingComponent<'a, ID, EntDepot, IDGen> = Box<
+    dyn FnOnce(&mut World<ID, EntDepot, IDGen>, Entity) -> Result<(), ComponentWriteError> + 'a,
+>;
+
+/// a builder for spawning an entity along with its components (see `World::spawn`)
+pub struct EntityBuilder<'a, ID: Clone + Eq + Hash, EntDepot: Depot<ID>, IDGen: Fn() -> ID> {
+    world: &'a mut World<ID, EntDepot, IDGen>,
+    components: Vec<PendingComponent<'a, ID, EntDepot, IDGen>>,
+}
+
+impl<'a, ID, EntDepot, IDGen> EntityBuilder<'a, ID, EntDepot, IDGen>
+where
+    ID: Clone + Eq + Hash,
+    EntDepot: Depot<ID>,
+    IDGen: Fn() -> ID,
+{
+    /// add a component for the entity to be spawned with (adding a second component of the
+    /// same type replaces the first)
+    pub fn with<T: 'static + Component>(mut self, component: T) -> Self {
+        self.components.push(Box::new(move |world, entity| {
+            world.insert_component(entity, component).map(|_| ())
+        }));
+        self
+    }
+
+    /// provision the entity and store every component for it
+    ///
+    /// if any component can't be stored, the components already stored are deleted and the
+    /// entity is freed again before the error is returned, so a failed spawn leaves no trace in
+    /// the world. if undoing the spawn fails as well, both errors are returned and the entity is
+    /// left in place, so that despawning it can be retried
+    pub fn build(self) -> Result<EntityProvision<ID>, SpawnError> {
+        let provision = self
+            .world
+            .provision_entity()
+            .map_err(SpawnError::Provision)?;
+        for insert in self.components {
+            if let Err(error) = insert(self.world, provision.entity) {
+                // the index was free, so every component stored at it is one just inserted
+                let index = provision.entity.index;
+                let rollback = match self.world.component_storage_set.purge_components(index) {
+                    Ok(_) => self.world.free_entity(provision.entity).map(|_| ()),
+                    Err(err) => Err(DespawnError::Component(err)),
+                };
+                return Err(match rollback {
+                    Ok(()) => SpawnError::Component(error),
+                    Err(rollback) => SpawnError::Rollback {
+                        error,
+                        rollback: Box::new(rollback),
+                        entity: provision.entity,
+                    },
+                });
+            }
+        }
+        Ok(provision)
+    }
+}
+
 pub struct World<ID, EntDepot, IDGen>
 where
     ID: Clone + Eq + Hash,
@@ -44,7 +245,11 @@ where
     IDGen: Fn() -> ID,
 {
     ids: EntDepot,
+    // the highest index the depot has handed out plus one, since depots don't expose their size
+    id_slots: usize,
     id_to_index: HashMap<ID, usize>,
+    // the current generation of every index the depot has handed out
+    generations: Vec<u32>,
     component_storage_set: ComponentStorageSet,
     entity_id_generator: IDGen,
 }
@@ -55,6 +260,65 @@ where
     EntDepot: Depot<ID>,
     IDGen: Fn() -> ID,
 {
+    /// create a new, empty `World` that keeps entity IDs in a depot and generates them with a
+    /// function
+    pub fn new(ids: EntDepot, entity_id_generator: IDGen) -> Self {
+        Self {
+            ids,
+            id_slots: 0,
+            id_to_index: HashMap::new(),
+            generations: Vec::new(),
+            component_storage_set: ComponentStorageSet::default(),
+            entity_id_generator,
+        }
+    }
+
+    /// check whether an entity handle refers to an entity that still exists
+    pub fn is_alive(&self, entity: Entity) -> bool {
+        self.generations.get(entity.index) == Some(&entity.generation)
+            && self.ids.get(entity.index).is_some()
+    }
+
+    /// get the handle of the entity with an ID, if it exists
+    pub fn entity_with_id(&self, id: &ID) -> Option<Entity> {
+        let index = *self.id_to_index.get(id)?;
+        Some(Entity {
+            index,
+            generation: self.generations[index],
+        })
+    }
+
+    /// get a view of an entity for reading its components (or `None` if the handle is stale)
+    pub fn entity(&self, entity: Entity) -> Option<EntityRef<'_, ID, EntDepot, IDGen>> {
+        self.is_alive(entity).then_some(EntityRef {
+            entity,
+            world: self,
+        })
+    }
+
+    /// get a view of an entity for reading and modifying its components (or `None` if the
+    /// handle is stale)
+    pub fn entity_mut(&mut self, entity: Entity) -> Option<EntityMut<'_, ID, EntDepot, IDGen>> {
+        if !self.is_alive(entity) {
+            return None;
+        }
+        Some(EntityMut {
+            entity,
+            world: self,
+        })
+    }
+
+    /// get the set of storages for every registered component type (e.g. to run operations
+    /// across every storage without knowing the component types)
+    pub fn component_storage_set(&self) -> &ComponentStorageSet {
+        &self.component_storage_set
+    }
+
+    /// get the set of storages for every registered component type mutably
+    pub fn component_storage_set_mut(&mut self) -> &mut ComponentStorageSet {
+        &mut self.component_storage_set
+    }
+
     pub fn register_component<T: 'static + Component, Storage: 'static + ComponentStorage<T>>(
         &mut self,
         component_storage: Storage,
@@ -63,33 +327,714 @@ where
             .insert_component_storage(component_storage);
     }
 
+    /// register every component type in a set (a tuple of component types that declare their
+    /// default storage, e.g. with `#[component(storage = ...)]`), each with a new, empty
+    /// storage of its declared type
+    pub fn register_components<C: ComponentSet>(&mut self) {
+        C::insert_default_storages(&mut self.component_storage_set);
+    }
+
+    /// store a component for an entity, returning the component it replaced (if any)
     pub fn insert_component<T: 'static + Component>(
         &mut self,
-        index: usize,
+        entity: Entity,
         component: T,
     ) -> Result<Option<T>, ComponentWriteError> {
+        if !self.is_alive(entity) {
+            return Err(ComponentWriteError::new_with_kind::<T>(
+                entity.index,
+                ComponentWriteErrorKind::StaleEntity,
+            ));
+        }
         if let Some(storage) = self.component_storage_set.get_component_storage_mut::<T>() {
-            return storage.insert(index, component);
+            return storage.insert(entity.index, component);
         }
 
-        Err(ComponentWriteError::new_with_detail::<T>(
-            index,
-            "component storage not found",
+        Err(ComponentWriteError::new_with_kind::<T>(
+            entity.index,
+            ComponentWriteErrorKind::StorageNotRegistered,
+        ))
+    }
+
+    /// get an entity's component of a type as an `Entry`, to insert or modify it in place
+    pub fn entry<T: 'static + Component>(
+        &mut self,
+        entity: Entity,
+    ) -> Result<Entry<'_, T>, ComponentWriteError> {
+        if !self.is_alive(entity) {
+            return Err(ComponentWriteError::new_with_kind::<T>(
+                entity.index,
+                ComponentWriteErrorKind::StaleEntity,
+            ));
+        }
+        if let Some(storage) = self.component_storage_set.get_component_storage_mut::<T>() {
+            return storage.entry(entity.index);
+        }
+
+        Err(ComponentWriteError::new_with_kind::<T>(
+            entity.index,
+            ComponentWriteErrorKind::StorageNotRegistered,
         ))
     }
 
     pub fn provision_entity(&mut self) -> Result<EntityProvision<ID>, String> {
         let id = (self.entity_id_generator)();
+        if self.id_to_index.contains_key(&id) {
+            return Err(String::from(
+                "entity ID generator returned an ID that is already in use",
+            ));
+        }
         match self.ids.put(id.clone()) {
             Ok(index) => {
-                if self.id_to_index.insert(id.clone(), index).is_none() {
-                    return Err(String::from(
-                        "failed to insert entry to map entity ID to entity index",
-                    ));
+                self.id_slots = self.id_slots.max(index + 1);
+                if index >= self.generations.len() {
+                    self.generations.resize(index + 1, 0);
                 }
-                Ok(EntityProvision { index, id })
+                self.id_to_index.insert(id.clone(), index);
+                let entity = Entity {
+                    index,
+                    generation: self.generations[index],
+                };
+                Ok(EntityProvision { entity, id })
             }
             Err(e) => Err(e.to_string()),
         }
     }
+
+    /// start spawning an entity, adding its components with `with` and finishing with `build`
+    /// (which stores either all of them or, if any fails, none of them)
+    pub fn spawn(&mut self) -> EntityBuilder<'_, ID, EntDepot, IDGen> {
+        EntityBuilder {
+            world: self,
+            components: Vec::new(),
+        }
+    }
+
+    /// destroy an entity, deleting its component from every registered storage and freeing
+    /// its index to be reused by the next entity provisioned, returning the ID it had (the
+    /// handle is stale from then on)
+    ///
+    /// if a storage fails to delete the entity's component, the entity is left in place
+    /// (along with any components not yet deleted), so that despawning it can be retried
+    pub fn despawn(&mut self, entity: Entity) -> Result<ID, DespawnError> {
+        if !self.is_alive(entity) {
+            return Err(DespawnError::StaleEntity);
+        }
+        self.component_storage_set
+            .delete_components(entity.index)
+            .map_err(DespawnError::Component)?;
+        self.free_entity(entity)
+    }
+
+    /// free the index of a live entity whose components were already deleted, returning the
+    /// ID it had
+    fn free_entity(&mut self, entity: Entity) -> Result<ID, DespawnError> {
+        let Some(id) = self.ids.get(entity.index).cloned() else {
+            return Err(DespawnError::StaleEntity);
+        };
+        if let Err(e) = self.ids.delete(entity.index) {
+            return Err(DespawnError::Depot(e.to_string()));
+        }
+        self.id_to_index.remove(&id);
+        self.generations[entity.index] = entity.generation.wrapping_add(1);
+        Ok(id)
+    }
+
+    /// report how much memory the world is using, broken down into the storage for each
+    /// component type and the bookkeeping for entities
+    pub fn memory_usage(&self) -> WorldMemoryUsage
+    where
+        EntDepot: DepotMemoryUsage,
+    {
+        WorldMemoryUsage {
+            components: self.component_storage_set.memory_usage(),
+            id_to_index: MemoryUsage::new::<HashMap<ID, usize>>(hash_map_heap_bytes::<ID, usize>(
+                self.id_to_index.capacity(),
+            )),
+            generations: MemoryUsage::new::<Vec<u32>>(vec_heap_bytes::<u32>(
+                self.generations.capacity(),
+            )),
+            ids: self.ids.memory_usage(self.id_slots),
+        }
+    }
+}
+
+#[cfg(test)]
+use crate::component::testing::TestComponent;
+#[cfg(test)]
+use crate::component::vec_component_storage::VecComponentStorage;
+
+#[cfg(test)]
+use crate::component::array_component_storage::ArrayComponentStorage;
+#[cfg(test)]
+use crate::component::hash_map_component_storage::HashMapComponentStorage;
+
+#[cfg(all(test, feature = "derive"))]
+use crate::component::DefaultStorage;
+
+#[cfg(all(test, feature = "derive"))]
+#[derive(Component, Debug, Default, PartialEq)]
+#[component(storage = VecComponentStorage)]
+struct Health(u32);
+
+#[cfg(all(test, feature = "derive"))]
+#[derive(Component, Debug, PartialEq)]
+#[component(storage = ArrayComponentStorage<Self, 16>)]
+struct Mana(u32);
+
+#[cfg(all(test, feature = "derive"))]
+#[derive(Component, Debug)]
+struct Unstored;
+
+#[cfg(test)]
+#[derive(Debug, PartialEq)]
+struct Shield(u8);
+
+#[cfg(test)]
+impl Component for Shield {}
+
+#[cfg(test)]
+#[derive(Debug)]
+struct Unregistered;
+
+#[cfg(test)]
+impl Component for Unregistered {}
+
+/// a storage whose components can't be deleted, to test what happens when cleaning up fails
+#[cfg(test)]
+#[derive(Default)]
+struct UndeletableStorage(VecComponentStorage<Shield>);
+
+#[cfg(test)]
+impl ComponentStorage<Shield> for UndeletableStorage {
+    fn get(&self, index: usize) -> Option<&Shield> {
+        self.0.get(index)
+    }
+
+    fn get_mut(&mut self, index: usize) -> Option<&mut Shield> {
+        self.0.get_mut(index)
+    }
+
+    fn insert(
+        &mut self,
+        index: usize,
+        component: Shield,
+    ) -> Result<Option<Shield>, ComponentWriteError> {
+        self.0.insert(index, component)
+    }
+
+    fn delete(&mut self, index: usize) -> Result<Option<Shield>, ComponentWriteError> {
+        Err(ComponentWriteError::new_with_kind::<Shield>(
+            index,
+            ComponentWriteErrorKind::Rejected,
+        ))
+    }
+
+    fn entry(&mut self, index: usize) -> Result<Entry<'_, Shield>, ComponentWriteError> {
+        self.0.entry(index)
+    }
+
+    fn iter(&self) -> crate::component::ComponentIter<'_, Shield> {
+        self.0.iter()
+    }
+
+    fn iter_mut(&mut self) -> crate::component::ComponentIterMut<'_, Shield> {
+        self.0.iter_mut()
+    }
+
+    fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    fn clear(&mut self) {
+        self.0.clear()
+    }
+
+    fn capacity(&self) -> usize {
+        self.0.capacity()
+    }
+
+    fn reserve(&mut self, additional: usize) {
+        self.0.reserve(additional)
+    }
+
+    fn shrink_to_fit(&mut self) {
+        self.0.shrink_to_fit()
+    }
+}
+
+/// create a world for tests, which numbers entity IDs in order
+#[cfg(test)]
+fn new_test_world() -> World<u32, VecDepot<u32>, impl Fn() -> u32> {
+    let next_id = std::cell::Cell::new(0);
+    World::new(VecDepot::default(), move || {
+        let id = next_id.get();
+        next_id.set(id + 1);
+        id
+    })
+}
+
+/// provision an entity in a test world, panicking if it fails
+#[cfg(test)]
+fn provision_test_entity(world: &mut World<u32, VecDepot<u32>, impl Fn() -> u32>) -> Entity {
+    match world.provision_entity() {
+        Ok(provision) => provision.entity,
+        Err(err) => panic!("provisioning an entity returned error {:?}", err),
+    }
+}
+
+#[test]
+fn can_modify_components_through_entity_mut() {
+    let mut world = new_test_world();
+    world.register_component(VecComponentStorage::<TestComponent>::default());
+    let entity = provision_test_entity(&mut world);
+    assert!(
+        world.insert_component(entity, TestComponent(1)).is_ok(),
+        "failed to insert a component into a registered storage"
+    );
+    match world
+        .entity_mut(entity)
+        .and_then(|mut entity| entity.get_component_mut::<TestComponent>().map(|c| c.0 = 2))
+    {
+        Some(()) => {}
+        None => panic!("get_component_mut returned None for a component known to exist"),
+    }
+    assert_eq!(
+        world
+            .entity(entity)
+            .and_then(|entity| entity.get_component::<TestComponent>().copied()),
+        Some(TestComponent(2)),
+        "change made through get_component_mut was not persisted"
+    );
+}
+
+#[test]
+fn can_insert_and_modify_components_through_entry() {
+    let mut world = new_test_world();
+    let entity = provision_test_entity(&mut world);
+    match world.entry::<TestComponent>(entity) {
+        Ok(entry) => panic!(
+            "getting an entry for an unregistered component type returned {:?}",
+            entry
+        ),
+        Err(err) => assert_eq!(err.kind(), ComponentWriteErrorKind::StorageNotRegistered),
+    }
+    world.register_component(VecComponentStorage::<TestComponent>::default());
+    for _ in 0..3 {
+        match world.entry::<TestComponent>(entity) {
+            Ok(entry) => entry.or_default().0 += 1,
+            Err(err) => panic!("getting an entry returned error {:?}", err),
+        }
+    }
+    assert_eq!(
+        world
+            .entity(entity)
+            .and_then(|entity| entity.get_component::<TestComponent>().copied()),
+        Some(TestComponent(3)),
+        "changes made through entry were not persisted"
+    );
+}
+
+#[test]
+fn reports_unregistered_storage_on_insert() {
+    let mut world = new_test_world();
+    let entity = provision_test_entity(&mut world);
+    match world.insert_component(entity, TestComponent(1)) {
+        Ok(x) => panic!("inserting into an unregistered storage returned {:?}", x),
+        Err(err) => assert_eq!(
+            err.kind(),
+            ComponentWriteErrorKind::StorageNotRegistered,
+            "inserting into an unregistered storage returned the wrong kind of error"
+        ),
+    }
+}
+
+#[cfg(feature = "derive")]
+#[test]
+fn can_register_derived_components_with_declared_storage() {
+    let mut world = new_test_world();
+    world.register_components::<(Health, Mana)>();
+    let entities: Vec<Entity> = (0..21).map(|_| provision_test_entity(&mut world)).collect();
+    assert!(
+        world.insert_component(entities[3], Health(10)).is_ok(),
+        "failed to insert a component into its declared storage"
+    );
+    match world.insert_component(entities[20], Mana(5)) {
+        Ok(x) => panic!(
+            "inserting past the end of the declared array storage returned {:?}",
+            x
+        ),
+        Err(err) => assert_eq!(
+            err.kind(),
+            ComponentWriteErrorKind::OutOfBounds { capacity: 16 },
+            "the declared storage type was not used"
+        ),
+    }
+    assert!(world.insert_component(entities[15], Mana(5)).is_ok());
+    assert_eq!(
+        world
+            .entity(entities[3])
+            .and_then(|entity| entity.get_component::<Health>()),
+        Some(&Health(10))
+    );
+    assert_eq!(
+        world
+            .entity(entities[15])
+            .and_then(|entity| entity.get_component::<Mana>()),
+        Some(&Mana(5))
+    );
+    assert!(world.insert_component(entities[0], Unstored).is_err());
+}
+
+#[cfg(feature = "derive")]
+#[test]
+fn derives_default_storage_type() {
+    fn storage_of<T: DefaultStorage>() -> T::Storage {
+        T::Storage::default()
+    }
+    let _: VecComponentStorage<Health> = storage_of::<Health>();
+    let _: ArrayComponentStorage<Mana, 16> = storage_of::<Mana>();
+}
+
+#[test]
+fn can_provision_entities_with_unique_ids() {
+    let next_id = std::cell::Cell::new(0u32);
+    let mut world = World::new(VecDepot::<u32>::default(), || {
+        next_id.set(next_id.get() + 1);
+        next_id.get() / 2
+    });
+    for expected in 0..2 {
+        match world.provision_entity() {
+            Ok(provision) => assert_eq!(
+                (provision.entity.index(), provision.id),
+                (expected, expected as u32)
+            ),
+            Err(err) => panic!("provisioning an entity returned error {:?}", err),
+        }
+    }
+    assert!(
+        world.provision_entity().is_err(),
+        "provisioning an entity with an ID already in use succeeded"
+    );
+    match world.provision_entity() {
+        Ok(provision) => {
+            assert_eq!((provision.entity.index(), provision.id), (2, 2));
+            assert_eq!(world.entity_with_id(&2), Some(provision.entity));
+        }
+        Err(err) => panic!("provisioning an entity returned error {:?}", err),
+    }
+}
+
+#[test]
+fn can_despawn_entities() {
+    let mut world = new_test_world();
+    world.register_component(VecComponentStorage::<TestComponent>::default());
+    world.register_component(HashMapComponentStorage::<Shield>::default());
+    let mut provisions = Vec::new();
+    for value in 0..2 {
+        let provision = match world.provision_entity() {
+            Ok(provision) => provision,
+            Err(err) => panic!("provisioning an entity returned error {:?}", err),
+        };
+        assert!(world
+            .insert_component(provision.entity, TestComponent(value))
+            .is_ok());
+        assert!(world
+            .insert_component(provision.entity, Shield(value as u8))
+            .is_ok());
+        provisions.push(provision);
+    }
+
+    assert_eq!(
+        world.entity(provisions[0].entity).map(|entity| entity
+            .debug_components()
+            .into_iter()
+            .map(|(_, component)| format!("{:?}", component))
+            .collect::<Vec<_>>()),
+        Some(["TestComponent(0)", "Shield(0)"].map(String::from).to_vec()),
+        "the entity's components were not all found"
+    );
+
+    match world.despawn(provisions[0].entity) {
+        Ok(id) => assert_eq!(id, provisions[0].id),
+        Err(err) => panic!("despawning an entity returned error {:?}", err),
+    }
+    assert!(!world.is_alive(provisions[0].entity));
+    assert!(world.entity(provisions[0].entity).is_none());
+    assert!(world.entity_with_id(&provisions[0].id).is_none());
+    assert_eq!(
+        world
+            .component_storage_set()
+            .erased_storages()
+            .map(|storage| storage.len())
+            .collect::<Vec<_>>(),
+        [1, 1],
+        "the despawned entity's components were not deleted"
+    );
+    assert_eq!(
+        world
+            .entity(provisions[1].entity)
+            .and_then(|entity| entity.get_component::<TestComponent>().copied()),
+        Some(TestComponent(1)),
+        "despawning an entity deleted another entity's component"
+    );
+    match world.despawn(provisions[0].entity) {
+        Ok(id) => panic!("despawning an entity twice returned {:?}", id),
+        Err(err) => assert!(
+            matches!(err, DespawnError::StaleEntity),
+            "despawning an entity twice returned the wrong error {:?}",
+            err
+        ),
+    }
+
+    let entity = provision_test_entity(&mut world);
+    assert_eq!(
+        entity.index(),
+        provisions[0].entity.index(),
+        "the despawned entity's index was not reused"
+    );
+    assert!(world
+        .component_storage_set()
+        .debug_components(entity.index())
+        .is_empty());
+}
+
+#[test]
+fn detects_stale_entity_handles() {
+    let mut world = new_test_world();
+    world.register_component(VecComponentStorage::<TestComponent>::default());
+    let provision = match world.provision_entity() {
+        Ok(provision) => provision,
+        Err(err) => panic!("provisioning an entity returned error {:?}", err),
+    };
+    let stale = provision.entity;
+    assert!(world.despawn(provision.entity).is_ok());
+    let entity = provision_test_entity(&mut world);
+    assert_eq!(entity.index(), stale.index());
+    assert_ne!(
+        entity.generation(),
+        stale.generation(),
+        "reusing an index did not start a new generation"
+    );
+    assert!(world.insert_component(entity, TestComponent(7)).is_ok());
+
+    assert!(world.is_alive(entity));
+    assert!(!world.is_alive(stale));
+    assert!(
+        world.entity(stale).is_none(),
+        "a stale handle gave access to the entity that reused its index"
+    );
+    assert!(world.entity_mut(stale).is_none());
+    match world.insert_component(stale, TestComponent(1)) {
+        Ok(x) => panic!("inserting through a stale handle returned {:?}", x),
+        Err(err) => assert_eq!(err.kind(), ComponentWriteErrorKind::StaleEntity),
+    }
+    match world.entry::<TestComponent>(stale) {
+        Ok(entry) => panic!(
+            "getting an entry through a stale handle returned {:?}",
+            entry
+        ),
+        Err(err) => assert_eq!(err.kind(), ComponentWriteErrorKind::StaleEntity),
+    }
+    assert_eq!(
+        world
+            .entity(entity)
+            .and_then(|entity| entity.get_component::<TestComponent>().copied()),
+        Some(TestComponent(7)),
+        "the entity that reused the index was changed through a stale handle"
+    );
+    let never_provisioned = Entity {
+        index: 5,
+        generation: 0,
+    };
+    assert!(!world.is_alive(never_provisioned));
+}
+
+#[test]
+fn can_spawn_entities_with_components() {
+    let mut world = new_test_world();
+    world.register_component(VecComponentStorage::<TestComponent>::default());
+    world.register_component(HashMapComponentStorage::<Shield>::default());
+    let provision = match world.spawn().with(TestComponent(4)).with(Shield(2)).build() {
+        Ok(provision) => provision,
+        Err(err) => panic!("spawning an entity returned error {:?}", err),
+    };
+    assert_eq!(world.entity_with_id(&provision.id), Some(provision.entity));
+    let Some(entity) = world.entity(provision.entity) else {
+        panic!("the spawned entity was not alive");
+    };
+    assert_eq!(
+        entity.get_component::<TestComponent>(),
+        Some(&TestComponent(4))
+    );
+    assert_eq!(entity.get_component::<Shield>(), Some(&Shield(2)));
+    assert!(
+        world.spawn().build().is_ok(),
+        "spawning an empty entity failed"
+    );
+}
+
+#[test]
+fn rolls_back_failed_spawns() {
+    let mut world = new_test_world();
+    world.register_component(VecComponentStorage::<TestComponent>::default());
+    world.register_component(ArrayComponentStorage::<Shield, 1>::default());
+    assert!(world.spawn().with(Shield(1)).build().is_ok());
+
+    match world.spawn().with(TestComponent(1)).with(Shield(2)).build() {
+        Ok(provision) => panic!(
+            "spawning past the end of an array storage returned {:?}",
+            provision.entity
+        ),
+        Err(SpawnError::Component(err)) => assert_eq!(
+            err.kind(),
+            ComponentWriteErrorKind::OutOfBounds { capacity: 1 }
+        ),
+        Err(err) => panic!("spawning returned the wrong error {:?}", err),
+    }
+    match world
+        .spawn()
+        .with(TestComponent(1))
+        .with(Unregistered)
+        .build()
+    {
+        Ok(provision) => panic!(
+            "spawning with an unregistered component returned {:?}",
+            provision.entity
+        ),
+        Err(SpawnError::Component(err)) => {
+            assert_eq!(err.kind(), ComponentWriteErrorKind::StorageNotRegistered)
+        }
+        Err(err) => panic!("spawning returned the wrong error {:?}", err),
+    }
+    assert_eq!(
+        world
+            .component_storage_set()
+            .erased_storages()
+            .map(|storage| storage.len())
+            .sum::<usize>(),
+        1,
+        "components stored before a spawn failed were not deleted"
+    );
+    assert_eq!(
+        provision_test_entity(&mut world).index(),
+        1,
+        "the index provisioned for a failed spawn was not freed"
+    );
+}
+
+#[test]
+fn reports_failure_to_roll_back_spawn() {
+    let mut world = new_test_world();
+    world.register_component(VecComponentStorage::<TestComponent>::default());
+    world.register_component(UndeletableStorage::default());
+    let entity = match world
+        .spawn()
+        .with(TestComponent(1))
+        .with(Shield(1))
+        .with(Unregistered)
+        .build()
+    {
+        Ok(provision) => panic!(
+            "spawning with an unregistered component returned {:?}",
+            provision.entity
+        ),
+        Err(SpawnError::Rollback {
+            error,
+            rollback,
+            entity,
+        }) => {
+            assert_eq!(error.kind(), ComponentWriteErrorKind::StorageNotRegistered);
+            match *rollback {
+                DespawnError::Component(err) => {
+                    assert_eq!(err.kind(), ComponentWriteErrorKind::Rejected)
+                }
+                err => panic!("undoing the spawn returned the wrong error {:?}", err),
+            }
+            entity
+        }
+        Err(err) => panic!("spawning returned the wrong error {:?}", err),
+    };
+    assert!(
+        world.is_alive(entity),
+        "an entity that still has components was freed"
+    );
+    assert_eq!(
+        world
+            .entity(entity)
+            .map(|entity| entity.debug_components().len()),
+        Some(1),
+        "the components that could be deleted were left in place"
+    );
+}
+
+#[test]
+fn reports_failure_to_provision_spawned_entity() {
+    let mut world = World::new(ArrayDepot::<u32, 1>::default(), || 0);
+    world.register_component(VecComponentStorage::<TestComponent>::default());
+    assert!(world.spawn().with(TestComponent(1)).build().is_ok());
+    match world.spawn().with(TestComponent(2)).build() {
+        Ok(provision) => panic!("spawning into a full depot returned {:?}", provision.entity),
+        Err(err) => assert!(
+            matches!(err, SpawnError::Provision(_)),
+            "spawning into a full depot returned the wrong error {:?}",
+            err
+        ),
+    }
+}
+
+#[test]
+fn reports_memory_usage_for_components_and_entities() {
+    let mut world = new_test_world();
+    let empty = world.memory_usage();
+    assert!(empty.components.storages.is_empty());
+    assert_eq!(empty.id_to_index.heap_bytes, 0);
+    assert_eq!(empty.generations.heap_bytes, 0);
+    assert_eq!(empty.ids.heap_bytes, 0);
+
+    world.register_component(VecComponentStorage::<TestComponent>::default());
+    for _ in 0..10 {
+        let entity = provision_test_entity(&mut world);
+        assert!(world.insert_component(entity, TestComponent(1)).is_ok());
+    }
+    let usage = world.memory_usage();
+    assert_eq!(usage.components.storages.len(), 1);
+    let storage = usage.components.storages[0];
+    assert_eq!(
+        storage.component_type,
+        std::any::type_name::<TestComponent>()
+    );
+    assert!(storage.usage.heap_bytes >= 10 * std::mem::size_of::<Option<TestComponent>>());
+    assert!(usage.id_to_index.heap_bytes > 0);
+    assert!(usage.generations.heap_bytes >= 10 * std::mem::size_of::<u32>());
+    assert_eq!(usage.ids.heap_bytes, 16 * std::mem::size_of::<Space<u32>>());
+    assert_eq!(
+        usage.total(),
+        usage.components.total() + usage.id_to_index + usage.generations + usage.ids
+    );
+}
+
+#[test]
+fn estimates_depot_memory_usage() {
+    let slot_size = std::mem::size_of::<Space<u32>>();
+    let depot = VecDepot::<u32>::default();
+    // a vector grown the same way as the depot's, to compare against its real capacity
+    let mut slots: Vec<Space<u32>> = Vec::new();
+    for slot_count in 0..100 {
+        assert_eq!(
+            depot.memory_usage(slot_count).heap_bytes,
+            slots.capacity() * slot_size,
+            "the depot's capacity was wrong after {} slots",
+            slot_count
+        );
+        slots.push(Space::Full(0));
+    }
+    let depot = VecDepot::<[u8; 2000]>::default();
+    assert_eq!(
+        depot.memory_usage(1).heap_bytes,
+        std::mem::size_of::<Space<[u8; 2000]>>()
+    );
+    let depot = ArrayDepot::<u32, 16>::default();
+    assert_eq!(depot.memory_usage(16).heap_bytes, 0);
+    assert!(depot.memory_usage(16).inline_bytes >= 16 * slot_size);
 }
@@ -1,4 +1,7 @@
 //! a high-performance entity-component-system library
 
+// lets code generated by `wyrd-derive` refer to `::wyrd` from inside this crate too
+extern crate self as wyrd;
+
 pub mod component;
 pub mod entity;
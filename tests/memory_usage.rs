@@ -0,0 +1,88 @@
+//! checks the heap size helpers against what collections actually allocate
+//!
+//! these tests replace the global allocator, so they live in their own test binary
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::collections::{BTreeMap, HashMap};
+use wyrd::component::memory_usage::{b_tree_map_heap_bytes, hash_map_heap_bytes, vec_heap_bytes};
+
+/// an allocator that counts the bytes allocated by each thread, so tests can measure the
+/// memory used by a collection
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATED: Cell<isize> = const { Cell::new(0) };
+}
+
+// SAFETY: every call is forwarded to the system allocator unchanged
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ =
+            ALLOCATED.try_with(|allocated| allocated.set(allocated.get() + layout.size() as isize));
+        // SAFETY: upheld by the caller
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        let _ =
+            ALLOCATED.try_with(|allocated| allocated.set(allocated.get() - layout.size() as isize));
+        // SAFETY: upheld by the caller
+        unsafe { System.dealloc(ptr, layout) }
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// measure the bytes still allocated by the value a function returns
+fn measure<T>(f: impl FnOnce() -> T) -> (T, usize) {
+    let before = ALLOCATED.with(Cell::get);
+    let value = f();
+    let after = ALLOCATED.with(Cell::get);
+    (value, (after - before) as usize)
+}
+
+#[test]
+fn test_vec_heap_bytes() {
+    let (vec, measured) = measure(|| (0..1000u64).collect::<Vec<u64>>());
+    assert_eq!(vec_heap_bytes::<u64>(vec.capacity()), measured);
+}
+
+#[test]
+fn test_hash_map_heap_bytes() {
+    for len in [0usize, 1, 3, 4, 7, 8, 14, 15, 100, 1000, 5000] {
+        let (map, measured) = measure(|| {
+            (0..len)
+                .map(|i| (i, [0u8; 12]))
+                .collect::<HashMap<usize, [u8; 12]>>()
+        });
+        assert_eq!(
+            hash_map_heap_bytes::<usize, [u8; 12]>(map.capacity()),
+            measured,
+            "wrong size for a map of {} entries",
+            len
+        );
+    }
+}
+
+#[test]
+fn test_b_tree_map_heap_bytes() {
+    for len in [0usize, 1, 11, 12, 100, 1000, 10000] {
+        let (map, measured) = measure(|| {
+            let mut map = BTreeMap::new();
+            for i in 0..len {
+                map.insert(i, [0u8; 12]);
+            }
+            map
+        });
+        let estimate = b_tree_map_heap_bytes::<usize, [u8; 12]>(map.len());
+        assert!(
+            estimate.abs_diff(measured) <= measured / 50,
+            "estimate for a map of {} entries was {} bytes, but it used {}",
+            len,
+            estimate,
+            measured
+        );
+    }
+}
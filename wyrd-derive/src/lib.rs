@@ -0,0 +1,314 @@
+//! derive macros for the wyrd entity component system (re-exported by `wyrd` when its `derive`
+//! feature is enabled, so there's no need to depend on this crate directly)
+
+use proc_macro::TokenStream;
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::{format_ident, quote};
+use syn::{
+    parse_macro_input, parse_quote, Data, DeriveInput, Error, Fields, GenericParam, Index, Member,
+    PathArguments, Type,
+};
+
+/// derive `Component` for a type
+///
+/// the storage it is normally kept in can be declared with `#[component(storage = ...)]`,
+/// which also implements `DefaultStorage` so the type can be registered with
+/// `World::register_components`. the storage can be named without type arguments (e.g.
+/// `VecComponentStorage`, which is then stored as `VecComponentStorage<Self>`), or as a full
+/// type (e.g. `ArrayComponentStorage<Self, 64>`)
+#[proc_macro_derive(Component, attributes(component))]
+pub fn derive_component(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_component(input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+/// derive `SoaComponent` for a struct, generating a set of columns (one `Vec` per field) that
+/// lets it be stored in a `SoaComponentStorage`, along with `<Name>Slices` and
+/// `<Name>SlicesMut` structs that give access to each column as a slice, and `<Name>Ref` and
+/// `<Name>RefMut` structs that give access to the fields of one component
+///
+/// every field type must implement `Clone`, and the struct must also implement `Component`
+#[proc_macro_derive(SoaComponent)]
+pub fn derive_soa_component(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_soa_component(input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+fn expand_component(input: DeriveInput) -> Result<TokenStream2, Error> {
+    let mut storage: Option<Type> = None;
+    for attr in input
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("component"))
+    {
+        attr.parse_nested_meta(|meta| {
+            if !meta.path.is_ident("storage") {
+                return Err(meta.error("unknown component attribute (expected `storage`)"));
+            }
+            if storage.is_some() {
+                return Err(meta.error("the component storage was already declared"));
+            }
+            storage = Some(meta.value()?.parse()?);
+            Ok(())
+        })?;
+    }
+
+    let name = &input.ident;
+    let (impl_generics, type_generics, where_clause) = input.generics.split_for_impl();
+    let component = quote! {
+        impl #impl_generics ::wyrd::component::Component for #name #type_generics #where_clause {}
+    };
+    let Some(mut storage) = storage else {
+        return Ok(component);
+    };
+    // a storage named without type arguments is given the component as its only argument
+    if let Type::Path(path) = &mut storage {
+        if let Some(segment) = path.path.segments.last_mut() {
+            if segment.arguments.is_empty() {
+                segment.arguments = PathArguments::AngleBracketed(parse_quote!(<Self>));
+            }
+        }
+    }
+    Ok(quote! {
+        #component
+
+        impl #impl_generics ::wyrd::component::DefaultStorage for #name #type_generics
+        #where_clause
+        {
+            type Storage = #storage;
+        }
+    })
+}
+
+fn expand_soa_component(input: DeriveInput) -> Result<TokenStream2, Error> {
+    let fields = match &input.data {
+        Data::Struct(data) => &data.fields,
+        _ => {
+            return Err(Error::new_spanned(
+                &input.ident,
+                "SoaComponent can only be derived for structs",
+            ))
+        }
+    };
+    if fields.is_empty() {
+        return Err(Error::new_spanned(
+            &input.ident,
+            "SoaComponent can't be derived for structs without fields (use TagComponentStorage)",
+        ));
+    }
+    if let Some(GenericParam::Lifetime(lifetime)) = input
+        .generics
+        .params
+        .iter()
+        .find(|param| matches!(param, GenericParam::Lifetime(_)))
+    {
+        return Err(Error::new_spanned(
+            lifetime,
+            "SoaComponent can't be derived for structs that borrow data",
+        ));
+    }
+
+    let vis = &input.vis;
+    let name = &input.ident;
+    let columns_name = format_ident!("{}Columns", name);
+    let slices_name = format_ident!("{}Slices", name);
+    let slices_mut_name = format_ident!("{}SlicesMut", name);
+    let ref_name = format_ident!("{}Ref", name);
+    let ref_mut_name = format_ident!("{}RefMut", name);
+    let columns_doc = format!(
+        "the columns of a `SoaComponentStorage<{}>`, one per field",
+        name
+    );
+    let slices_doc = format!(
+        "every column of a `SoaComponentStorage<{}>` as a slice",
+        name
+    );
+    let slices_mut_doc = format!(
+        "every column of a `SoaComponentStorage<{}>` as a mutable slice",
+        name
+    );
+    let ref_doc = format!(
+        "the fields of one component in a `SoaComponentStorage<{}>`",
+        name
+    );
+    let ref_mut_doc = format!(
+        "the fields of one component in a `SoaComponentStorage<{}>`, mutably",
+        name
+    );
+
+    let members: Vec<Member> = fields
+        .iter()
+        .enumerate()
+        .map(|(i, field)| match &field.ident {
+            Some(ident) => Member::Named(ident.clone()),
+            None => Member::Unnamed(Index::from(i)),
+        })
+        .collect();
+    let types: Vec<_> = fields.iter().map(|field| &field.ty).collect();
+    let bindings: Vec<_> = (0..members.len())
+        .map(|i| format_ident!("__field{}", i))
+        .collect();
+    let first = &members[0];
+
+    let (impl_generics, type_generics, where_clause) = input.generics.split_for_impl();
+    let params = &input.generics.params;
+    let arguments: Vec<_> = params
+        .iter()
+        .filter_map(|param| match param {
+            GenericParam::Type(param) => Some(&param.ident),
+            GenericParam::Const(param) => Some(&param.ident),
+            // structs with lifetimes were rejected above
+            GenericParam::Lifetime(_) => None,
+        })
+        .collect();
+    let predicates = where_clause.map(|clause| &clause.predicates);
+    let lifetime = syn::Lifetime::new("'a", Span::call_site());
+
+    // mirror the shape of the component (named or tuple fields) in the generated structs
+    let declare = |field_types: Vec<TokenStream2>, field_vis: TokenStream2| match fields {
+        Fields::Named(_) => quote! {
+            #where_clause { #(#field_vis #members: #field_types,)* }
+        },
+        _ => quote! {
+            ( #(#field_vis #field_types,)* ) #where_clause;
+        },
+    };
+    let columns_body = declare(
+        types
+            .iter()
+            .map(|ty| quote!(::std::vec::Vec<#ty>))
+            .collect(),
+        quote!(),
+    );
+    let slices_body = declare(
+        types.iter().map(|ty| quote!(&#lifetime [#ty])).collect(),
+        quote!(pub),
+    );
+    let slices_mut_body = declare(
+        types
+            .iter()
+            .map(|ty| quote!(&#lifetime mut [#ty]))
+            .collect(),
+        quote!(pub),
+    );
+    let ref_body = declare(
+        types.iter().map(|ty| quote!(&#lifetime #ty)).collect(),
+        quote!(pub),
+    );
+    let ref_mut_body = declare(
+        types.iter().map(|ty| quote!(&#lifetime mut #ty)).collect(),
+        quote!(pub),
+    );
+
+    Ok(quote! {
+        #[doc = #columns_doc]
+        #vis struct #columns_name <#params> #columns_body
+
+        #[doc = #slices_doc]
+        #vis struct #slices_name <#lifetime, #params> #slices_body
+
+        #[doc = #slices_mut_doc]
+        #vis struct #slices_mut_name <#lifetime, #params> #slices_mut_body
+
+        #[doc = #ref_doc]
+        #vis struct #ref_name <#lifetime, #params> #ref_body
+
+        #[doc = #ref_mut_doc]
+        #vis struct #ref_mut_name <#lifetime, #params> #ref_mut_body
+
+        impl #impl_generics ::std::default::Default for #columns_name #type_generics
+        #where_clause
+        {
+            fn default() -> Self {
+                Self { #(#members: ::std::vec::Vec::new(),)* }
+            }
+        }
+
+        impl #impl_generics ::wyrd::component::soa_component_storage::SoaColumns<#name #type_generics>
+            for #columns_name #type_generics
+        where
+            #(#types: ::std::clone::Clone,)*
+            #predicates
+        {
+            type Slices<#lifetime> = #slices_name <#lifetime, #(#arguments,)*> where Self: #lifetime;
+            type SlicesMut<#lifetime> = #slices_mut_name <#lifetime, #(#arguments,)*> where Self: #lifetime;
+            type Ref<#lifetime> = #ref_name <#lifetime, #(#arguments,)*> where Self: #lifetime;
+            type RefMut<#lifetime> = #ref_mut_name <#lifetime, #(#arguments,)*> where Self: #lifetime;
+
+            fn len(&self) -> usize {
+                self.#first.len()
+            }
+
+            fn push(&mut self, component: #name #type_generics) {
+                let #name { #(#members: #bindings,)* } = component;
+                #(self.#members.push(#bindings);)*
+            }
+
+            fn swap_remove(&mut self, slot: usize) -> #name #type_generics {
+                #name { #(#members: self.#members.swap_remove(slot),)* }
+            }
+
+            fn replace(&mut self, slot: usize, component: #name #type_generics) -> #name #type_generics {
+                let #name { #(#members: #bindings,)* } = component;
+                #name { #(#members: ::std::mem::replace(&mut self.#members[slot], #bindings),)* }
+            }
+
+            fn read(&self, slot: usize) -> #name #type_generics {
+                #name { #(#members: ::std::clone::Clone::clone(&self.#members[slot]),)* }
+            }
+
+            fn write(&mut self, slot: usize, component: &#name #type_generics) {
+                #(::std::clone::Clone::clone_from(&mut self.#members[slot], &component.#members);)*
+            }
+
+            fn clear(&mut self) {
+                #(self.#members.clear();)*
+            }
+
+            fn capacity(&self) -> usize {
+                ::std::primitive::usize::MAX #(.min(self.#members.capacity()))*
+            }
+
+            fn reserve(&mut self, additional: usize) {
+                #(self.#members.reserve(additional);)*
+            }
+
+            fn shrink_to_fit(&mut self) {
+                #(self.#members.shrink_to_fit();)*
+            }
+
+            fn heap_bytes(&self) -> ::std::primitive::usize {
+                0 #(+ self.#members.capacity() * ::std::mem::size_of::<#types>())*
+            }
+
+            fn slices(&self) -> Self::Slices<'_> {
+                #slices_name { #(#members: &self.#members,)* }
+            }
+
+            fn slices_mut(&mut self) -> Self::SlicesMut<'_> {
+                #slices_mut_name { #(#members: &mut self.#members,)* }
+            }
+
+            fn row(&self, slot: usize) -> Self::Ref<'_> {
+                #ref_name { #(#members: &self.#members[slot],)* }
+            }
+
+            fn row_mut(&mut self, slot: usize) -> Self::RefMut<'_> {
+                #ref_mut_name { #(#members: &mut self.#members[slot],)* }
+            }
+        }
+
+        impl #impl_generics ::wyrd::component::soa_component_storage::SoaComponent
+            for #name #type_generics
+        where
+            #(#types: ::std::clone::Clone,)*
+            #predicates
+        {
+            type Columns = #columns_name #type_generics;
+        }
+    })
+}